@@ -0,0 +1,203 @@
+use std::collections::{BTreeSet, HashMap};
+
+// Find every distinct set of `k` entries (by value) summing to `target`.
+// Each solution is sorted, and solutions are returned in ascending order.
+pub fn k_sum(values: &[u32], target: u64, k: usize) -> Vec<Vec<u32>> {
+    match k {
+        0 => {
+            if target == 0 {
+                vec![Vec::new()]
+            } else {
+                Vec::new()
+            }
+        }
+        1 => {
+            let found: BTreeSet<u32> = values.iter()
+                .filter(|&&v| v as u64 == target)
+                .cloned()
+                .collect();
+            found.into_iter().map(|v| vec![v]).collect()
+        }
+        2 => pair_sum(values, target),
+        3 => triple_sum(values, target),
+        _ => meet_in_the_middle(values, target, k),
+    }
+}
+
+// Hash based lookup: for each distinct value check if its complement was seen.
+fn pair_sum(values: &[u32], target: u64) -> Vec<Vec<u32>> {
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    for v in values {
+        *counts.entry(*v as u64).or_insert(0) += 1;
+    }
+
+    let mut rv = Vec::new();
+    for (&a, &count) in &counts {
+        if a > target {
+            continue;
+        }
+        let b = target - a;
+        if a < b && counts.contains_key(&b) || a == b && count >= 2 {
+            rv.push(vec![a as u32, b as u32]);
+        }
+    }
+    rv.sort();
+    rv
+}
+
+// Sort then walk two pointers for each fixed first element.
+fn triple_sum(values: &[u32], target: u64) -> Vec<Vec<u32>> {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+
+    let mut rv = Vec::new();
+    for i in 0..sorted.len() {
+        if i > 0 && sorted[i] == sorted[i - 1] {
+            continue;
+        }
+        let first = sorted[i] as u64;
+        if first > target {
+            break;
+        }
+        let rest = target - first;
+
+        let mut lo = i + 1;
+        let mut hi = sorted.len();
+        while lo + 1 < hi {
+            let sum = sorted[lo] as u64 + sorted[hi - 1] as u64;
+            if sum < rest {
+                lo += 1;
+            } else if sum > rest {
+                hi -= 1;
+            } else {
+                rv.push(vec![sorted[i], sorted[lo], sorted[hi - 1]]);
+                lo += 1;
+                while lo + 1 < hi && sorted[lo] == sorted[lo - 1] {
+                    lo += 1;
+                }
+                hi -= 1;
+            }
+        }
+    }
+    rv
+}
+
+// All index combinations of `size` taken from `0..n`, with the sum of their values.
+fn index_sums(values: &[u32], size: usize, target: u64) -> Vec<(Vec<usize>, u64)> {
+    let mut rv = Vec::new();
+    let mut stack: Vec<(Vec<usize>, u64)> = vec![(Vec::new(), 0)];
+
+    while let Some((indexes, sum)) = stack.pop() {
+        if indexes.len() == size {
+            rv.push((indexes, sum));
+            continue;
+        }
+        let start = indexes.last().map_or(0, |&i| i + 1);
+        for (i, &v) in values.iter().enumerate().skip(start) {
+            let new_sum = sum + v as u64;
+            if new_sum > target {
+                continue;
+            }
+            let mut next = indexes.clone();
+            next.push(i);
+            stack.push((next, new_sum));
+        }
+    }
+    rv
+}
+
+// Split each k-set of indexes into its lowest half and its highest half,
+// index all the low halves by sum and look the complement up for each high half.
+fn meet_in_the_middle(values: &[u32], target: u64, k: usize) -> Vec<Vec<u32>> {
+    let low_size = k / 2;
+    let high_size = k - low_size;
+
+    let mut lows: HashMap<u64, Vec<Vec<usize>>> = HashMap::new();
+    for (indexes, sum) in index_sums(values, low_size, target) {
+        lows.entry(sum).or_default().push(indexes);
+    }
+
+    let mut found = BTreeSet::new();
+    for (high, sum) in index_sums(values, high_size, target) {
+        let candidates = match lows.get(&(target - sum)) {
+            Some(c) => c,
+            None => continue,
+        };
+        for low in candidates {
+            // lowest halves only, so each set of indexes is seen once
+            if low.last().is_some_and(|&l| l >= high[0]) {
+                continue;
+            }
+            let mut solution: Vec<u32> = low.iter()
+                .chain(high.iter())
+                .map(|&i| values[i])
+                .collect();
+            solution.sort_unstable();
+            found.insert(solution);
+        }
+    }
+    found.into_iter().collect()
+}
+
+// Product of the entries, None if it does not fit.
+pub fn mult(v: &[u32]) -> Option<u64> {
+    v.iter().try_fold(1u64, |total, &n| total.checked_mul(n as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    static EXAMPLE: &[u32] = &[1721, 979, 366, 299, 675, 1456];
+
+    #[rstest(input, target, k, exp,
+    case(EXAMPLE, 2020, 1, vec![]),
+    case(EXAMPLE, 1721, 1, vec![vec![1721]]),
+    case(EXAMPLE, 2020, 2, vec![vec![299, 1721]]),
+    case(EXAMPLE, 2020, 3, vec![vec![366, 675, 979]]),
+    case(&[1, 2, 3, 4, 5], 6, 2, vec![vec![1, 5], vec![2, 4]]),
+    case(&[3, 3, 3], 6, 2, vec![vec![3, 3]]),
+    case(&[3], 6, 2, vec![]),
+    case(&[1, 1, 2, 2, 3, 3], 6, 3, vec![vec![1, 2, 3]]),
+    case(&[2, 2, 2], 6, 3, vec![vec![2, 2, 2]]),
+    case(&[1, 2, 3, 4, 5, 6], 10, 4, vec![vec![1, 2, 3, 4]]),
+    case(&[1, 2, 3, 4, 5, 6], 15, 5, vec![vec![1, 2, 3, 4, 5]]),
+    case(&[1, 2, 3, 4, 5, 6], 14, 4, vec![vec![1, 2, 5, 6], vec![1, 3, 4, 6], vec![2, 3, 4, 5]]),
+    case(&[u32::MAX, u32::MAX], 2 * u32::MAX as u64, 2, vec![vec![u32::MAX, u32::MAX]]),
+    )]
+    fn test_k_sum(input: &[u32], target: u64, k: usize, exp: Vec<Vec<u32>>) {
+        assert_eq!(k_sum(input, target, k), exp);
+    }
+
+    #[rstest(k, case(2), case(3), case(4), case(5))]
+    fn test_k_sum_matches_brute_force(k: usize) {
+        let values: Vec<u32> = (0..12).map(|i| (i * 7) % 11 + i).collect();
+        let target = 30;
+
+        // every subset of the indexes, one bit per index
+        let mut exp = BTreeSet::new();
+        for mask in 0u32..1 << values.len() {
+            if mask.count_ones() as usize != k {
+                continue;
+            }
+            let mut s: Vec<u32> = (0..values.len()).filter(|i| mask & 1 << i != 0).map(|i| values[i]).collect();
+            if s.iter().map(|&v| v as u64).sum::<u64>() == target {
+                s.sort_unstable();
+                exp.insert(s);
+            }
+        }
+        assert!(!exp.is_empty());
+        assert_eq!(k_sum(&values, target, k), exp.into_iter().collect::<Vec<_>>());
+    }
+
+    #[rstest(input, output,
+    case(&[299, 1721], Some(514579)),
+    case(&[366, 675, 979], Some(241861950)),
+    case(&[1 << 20, 1 << 20, 1 << 20], Some(1 << 60)),
+    case(&[u32::MAX, u32::MAX, u32::MAX], None),
+    )]
+    fn test_mult(input: &[u32], output: Option<u64>) {
+        assert_eq!(mult(input), output);
+    }
+}
@@ -2,6 +2,10 @@ use std::{io, env};
 use std::fs::File;
 use std::io::{BufReader, BufRead};
 
+mod policy;
+
+use policy::{parse_policy, CountRange, ExactlyOnePosition, PasswordPolicy};
+
 fn read(path: &str) -> io::Result<Vec<PwdEntry>> {
     let file = File::open(path)?;
     let br = BufReader::new(file);
@@ -16,8 +20,8 @@ fn read(path: &str) -> io::Result<Vec<PwdEntry>> {
 
 #[derive(Debug)]
 struct PwdEntry {
-    value_a: usize,  // Q1: min, Q2: index (1 base)
-    value_b: usize,  // Q1: max, Q2: index (1 base)
+    value_a: usize,  // meaning depends on the policy
+    value_b: usize,
     letter: char,
     password: String,
 }
@@ -25,8 +29,8 @@ struct PwdEntry {
 impl PwdEntry {
     fn from_string(line: String) -> PwdEntry {
         let line = line
-            .replace(":", "")
-            .replace("-", " ");
+            .replace(':', "")
+            .replace('-', " ");
         let fields: Vec<&str> = line.split_whitespace().collect();
         PwdEntry{
             value_a: fields[0].parse().unwrap(),
@@ -35,45 +39,37 @@ impl PwdEntry {
             password: fields[3].to_string(),
         }
     }
-
-    fn q1_is_valid(&self) -> bool {
-        let count = self.password.chars()
-            .filter(|&c| c == self.letter).count();
-        // eprintln!("Found {} {} in {:?}", count, self.letter, self);
-        self.value_a <= count && self.value_b >= count
-    }
-
-    fn q2_is_valid(&self) -> bool {
-        let letters: Vec<char> = self.password.chars().collect();
-        let mut found = 0;
-        if *letters.get(self.value_a - 1).unwrap_or(&' ') == self.letter {
-            found += 1;
-        }
-        if *letters.get(self.value_b - 1).unwrap_or(&' ') == self.letter {
-            found += 1;
-        }
-        // eprintln!("Found {} {} times at positions {:?}", self.letter, found, self);
-        return found == 1
-    }
 }
 
 fn main() {
     let path = env::args().nth(1).expect("please supply a path");
+    let show_report = env::args().any(|a| a == "--report");
     let contents = read(&path).expect("no content");
 
-    let mut valid_q1 = 0;
-    let mut valid_q2 = 0;
-    for r in &contents {
-        if r.q1_is_valid() {
-            valid_q1 += 1;
-        }
-        if r.q2_is_valid() {
-            valid_q2 += 1;
-        }
+    let mut policies: Vec<(String, Box<dyn PasswordPolicy>)> = vec![
+        ("Q1".to_string(), Box::new(CountRange)),
+        ("Q2".to_string(), Box::new(ExactlyOnePosition)),
+    ];
+    // extra policies, e.g. --policy="and(count, not(regex(^a)))"
+    for spec in env::args().filter_map(|a| a.strip_prefix("--policy=").map(|s| s.to_string())) {
+        let policy = parse_policy(&spec).expect("invalid policy");
+        policies.push((spec, policy));
     }
 
-    println!("Q1: {}/{} rules are valid", valid_q1, contents.len());
-    println!("Q2: {}/{} rules are valid", valid_q2, contents.len());
+    for (label, policy) in &policies {
+        let valid = contents.iter().filter(|r| policy.is_valid(r)).count();
+        println!("{}: {}/{} rules are valid", label, valid, contents.len());
+    }
+
+    if show_report {
+        for (idx, r) in contents.iter().enumerate() {
+            for (_, policy) in &policies {
+                if let Err(reason) = policy.check(r) {
+                    println!("line {}: fails {}: {}", idx + 1, policy.name(), reason);
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -86,14 +82,14 @@ mod tests {
     case(&"2-9 c: ccccccccc"),
     )]
     fn test_q1_valid(input: &str) {
-        assert!(PwdEntry::from_string(input.to_string()).q1_is_valid());
+        assert!(CountRange.is_valid(&PwdEntry::from_string(input.to_string())));
     }
 
     #[rstest(input,
     case(&"1-3 b: cdefg"),
     )]
     fn test_q1_invalid(input: &str) {
-        assert!(!PwdEntry::from_string(input.to_string()).q1_is_valid());
+        assert!(!CountRange.is_valid(&PwdEntry::from_string(input.to_string())));
     }
 
     #[rstest(input,
@@ -101,7 +97,7 @@ mod tests {
 
     )]
     fn test_q2_valid(input: &str) {
-        assert!(PwdEntry::from_string(input.to_string()).q2_is_valid());
+        assert!(ExactlyOnePosition.is_valid(&PwdEntry::from_string(input.to_string())));
     }
 
     #[rstest(input,
@@ -109,6 +105,6 @@ mod tests {
     case(&"2-9 c: ccccccccc"),
     )]
     fn test_q2_invalid(input: &str) {
-        assert!(!PwdEntry::from_string(input.to_string()).q2_is_valid());
+        assert!(!ExactlyOnePosition.is_valid(&PwdEntry::from_string(input.to_string())));
     }
 }
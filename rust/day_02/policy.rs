@@ -0,0 +1,275 @@
+use regex::Regex;
+
+use super::PwdEntry;
+
+// A rule a password line has to follow. Err explains why the line breaks it.
+pub trait PasswordPolicy {
+    fn name(&self) -> String;
+    fn check(&self, entry: &PwdEntry) -> Result<(), String>;
+
+    fn is_valid(&self, entry: &PwdEntry) -> bool {
+        self.check(entry).is_ok()
+    }
+}
+
+// Q1: the letter appears between value_a and value_b times.
+pub struct CountRange;
+
+impl PasswordPolicy for CountRange {
+    fn name(&self) -> String {
+        "count".to_string()
+    }
+
+    fn check(&self, entry: &PwdEntry) -> Result<(), String> {
+        let count = entry.password.chars()
+            .filter(|&c| c == entry.letter).count();
+        if entry.value_a <= count && entry.value_b >= count {
+            Ok(())
+        } else {
+            Err(format!("found {} {:?}, expected {} to {}", count, entry.letter, entry.value_a, entry.value_b))
+        }
+    }
+}
+
+// Q2: the letter is at exactly one of the 1 based positions value_a and value_b.
+pub struct ExactlyOnePosition;
+
+impl PasswordPolicy for ExactlyOnePosition {
+    fn name(&self) -> String {
+        "position".to_string()
+    }
+
+    fn check(&self, entry: &PwdEntry) -> Result<(), String> {
+        let letters: Vec<char> = entry.password.chars().collect();
+        let at = |position: usize| position > 0 && letters.get(position - 1) == Some(&entry.letter);
+        match (at(entry.value_a), at(entry.value_b)) {
+            (true, false) | (false, true) => Ok(()),
+            (true, true) => Err(format!("{:?} at both positions {} and {}", entry.letter, entry.value_a, entry.value_b)),
+            (false, false) => Err(format!("{:?} at neither position {} nor {}", entry.letter, entry.value_a, entry.value_b)),
+        }
+    }
+}
+
+// The password has to match a regular expression.
+pub struct RegexPolicy {
+    regex: Regex,
+}
+
+impl RegexPolicy {
+    pub fn new(pattern: &str) -> Result<RegexPolicy, regex::Error> {
+        Ok(RegexPolicy { regex: Regex::new(pattern)? })
+    }
+}
+
+impl PasswordPolicy for RegexPolicy {
+    fn name(&self) -> String {
+        format!("regex({})", self.regex)
+    }
+
+    fn check(&self, entry: &PwdEntry) -> Result<(), String> {
+        if self.regex.is_match(&entry.password) {
+            Ok(())
+        } else {
+            Err(format!("{:?} does not match {}", entry.password, self.regex))
+        }
+    }
+}
+
+pub struct And(pub Vec<Box<dyn PasswordPolicy>>);
+
+impl PasswordPolicy for And {
+    fn name(&self) -> String {
+        let names: Vec<String> = self.0.iter().map(|p| p.name()).collect();
+        format!("and({})", names.join(", "))
+    }
+
+    fn check(&self, entry: &PwdEntry) -> Result<(), String> {
+        let failures: Vec<String> = self.0.iter()
+            .filter_map(|p| p.check(entry).err().map(|e| format!("{}: {}", p.name(), e)))
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("; "))
+        }
+    }
+}
+
+pub struct Or(pub Vec<Box<dyn PasswordPolicy>>);
+
+impl PasswordPolicy for Or {
+    fn name(&self) -> String {
+        let names: Vec<String> = self.0.iter().map(|p| p.name()).collect();
+        format!("or({})", names.join(", "))
+    }
+
+    fn check(&self, entry: &PwdEntry) -> Result<(), String> {
+        let mut failures = Vec::new();
+        for p in &self.0 {
+            match p.check(entry) {
+                Ok(()) => return Ok(()),
+                Err(e) => failures.push(format!("{}: {}", p.name(), e)),
+            }
+        }
+        Err(failures.join("; "))
+    }
+}
+
+pub struct Not(pub Box<dyn PasswordPolicy>);
+
+impl PasswordPolicy for Not {
+    fn name(&self) -> String {
+        format!("not({})", self.0.name())
+    }
+
+    fn check(&self, entry: &PwdEntry) -> Result<(), String> {
+        match self.0.check(entry) {
+            Ok(()) => Err(format!("{} holds", self.0.name())),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+// Split `a, f(b, c), d` on the commas which are not nested in parentheses.
+fn split_arguments(value: &str) -> Vec<&str> {
+    let mut rv = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                rv.push(value[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    rv.push(value[start..].trim());
+    rv
+}
+
+// Build a policy from the same notation `name()` uses, e.g. `and(count, not(regex(^a+$)))`.
+pub fn parse_policy(spec: &str) -> Result<Box<dyn PasswordPolicy>, String> {
+    let spec = spec.trim();
+    match spec {
+        "count" => return Ok(Box::new(CountRange)),
+        "position" => return Ok(Box::new(ExactlyOnePosition)),
+        _ => {}
+    }
+
+    let open = spec.find('(').ok_or_else(|| format!("unknown policy {:?}", spec))?;
+    if !spec.ends_with(')') {
+        return Err(format!("missing ')' in {:?}", spec));
+    }
+    let (name, arguments) = (&spec[..open], &spec[open + 1..spec.len() - 1]);
+
+    let nested = || split_arguments(arguments)
+        .into_iter()
+        .map(parse_policy)
+        .collect::<Result<Vec<_>, String>>();
+    match name {
+        "regex" => Ok(Box::new(RegexPolicy::new(arguments).map_err(|e| e.to_string())?)),
+        "and" => Ok(Box::new(And(nested()?))),
+        "or" => Ok(Box::new(Or(nested()?))),
+        "not" => {
+            let mut inner = nested()?;
+            if inner.len() != 1 {
+                return Err(format!("not takes a single policy in {:?}", spec));
+            }
+            Ok(Box::new(Not(inner.remove(0))))
+        }
+        _ => Err(format!("unknown policy {:?}", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn entry(line: &str) -> PwdEntry {
+        PwdEntry::from_string(line.to_string())
+    }
+
+    #[rstest(input, exp,
+    case("1-3 a: abcde", Ok(())),
+    case("1-3 b: cdefg", Err("found 0 'b', expected 1 to 3")),
+    case("1-2 c: cccc", Err("found 4 'c', expected 1 to 2")),
+    )]
+    fn test_count_range(input: &str, exp: Result<(), &str>) {
+        assert_eq!(CountRange.check(&entry(input)), exp.map_err(|e| e.to_string()));
+    }
+
+    #[rstest(input, exp,
+    case("1-3 a: abcde", Ok(())),
+    case("1-3 b: cdefg", Err("'b' at neither position 1 nor 3")),
+    case("2-9 c: ccccccccc", Err("'c' at both positions 2 and 9")),
+    case("3-20 c: abc", Ok(())),
+    )]
+    fn test_exactly_one_position(input: &str, exp: Result<(), &str>) {
+        assert_eq!(ExactlyOnePosition.check(&entry(input)), exp.map_err(|e| e.to_string()));
+    }
+
+    #[rstest(pattern, input, exp,
+    case("^[a-z]+$", "1-3 a: abcde", true),
+    case("^[a-z]+$", "1-3 a: abc1e", false),
+    case("bb|dd", "1-3 a: abbde", true),
+    )]
+    fn test_regex(pattern: &str, input: &str, exp: bool) {
+        let policy = RegexPolicy::new(pattern).expect("valid regex");
+        assert_eq!(policy.is_valid(&entry(input)), exp);
+    }
+
+    #[rstest(input, q1, q2, both, either, neither,
+    case("1-3 a: abcde", true, true, true, true, false),
+    case("1-3 b: cdefg", false, false, false, false, true),
+    case("2-9 c: ccccccccc", true, false, false, true, false),
+    )]
+    fn test_composition(input: &str, q1: bool, q2: bool, both: bool, either: bool, neither: bool) {
+        let e = entry(input);
+        assert_eq!(CountRange.is_valid(&e), q1);
+        assert_eq!(ExactlyOnePosition.is_valid(&e), q2);
+        assert_eq!(And(vec![Box::new(CountRange), Box::new(ExactlyOnePosition)]).is_valid(&e), both);
+        assert_eq!(Or(vec![Box::new(CountRange), Box::new(ExactlyOnePosition)]).is_valid(&e), either);
+        let neither_policy = Not(Box::new(Or(vec![Box::new(CountRange), Box::new(ExactlyOnePosition)])));
+        assert_eq!(neither_policy.is_valid(&e), neither);
+    }
+
+    #[rstest(spec,
+    case("count"),
+    case("position"),
+    case("regex(^(ab|c)+$)"),
+    case("and(count, not(regex(^c+$)))"),
+    case("or(position, and(count, regex(x)))"),
+    )]
+    fn test_parse_policy(spec: &str) {
+        let policy = parse_policy(spec).expect("valid policy");
+        assert_eq!(policy.name(), spec);
+    }
+
+    #[rstest(spec,
+    case("size"),
+    case("and(count"),
+    case("not(count, position)"),
+    case("regex([a-)"),
+    case("xor(count, position)"),
+    )]
+    fn test_parse_invalid_policy(spec: &str) {
+        assert!(parse_policy(spec).is_err());
+    }
+
+    #[test]
+    fn test_composed_reasons() {
+        let policy = And(vec![
+            Box::new(CountRange),
+            Box::new(Not(Box::new(RegexPolicy::new("^c+$").unwrap()))),
+        ]);
+        assert_eq!(policy.name(), "and(count, not(regex(^c+$)))");
+        assert_eq!(
+            policy.check(&entry("2-4 c: ccccccccc")),
+            Err("count: found 9 'c', expected 2 to 4; not(regex(^c+$)): regex(^c+$) holds".to_string()),
+        );
+    }
+}
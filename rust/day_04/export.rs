@@ -0,0 +1,159 @@
+use std::io;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::collections::{BTreeMap, HashMap};
+
+use super::{Passport, Value};
+use super::schema::{FieldType, Presence, Schema};
+
+// The CSV columns: every field the schema keeps, in schema order, with a
+// measure split into <name>_value and <name>_unit.
+fn columns(schema: &Schema) -> Vec<String> {
+    schema.fields.iter()
+        .filter(|f| f.presence != Presence::Ignored)
+        .flat_map(|f| match f.kind {
+            FieldType::Measure => vec![format!("{}_value", f.name), format!("{}_unit", f.name)],
+            _ => vec![f.name.clone()],
+        })
+        .collect()
+}
+
+// Field values as they are written in the input file, measures split in two.
+fn to_row(p: &Passport) -> BTreeMap<String, String> {
+    let mut rv = BTreeMap::new();
+    for (name, value) in &p.fields {
+        match value {
+            Value::Measure(h) => {
+                rv.insert(format!("{}_value", name), h.value.to_string());
+                rv.insert(format!("{}_unit", name), h.unit.clone());
+            }
+            _ => { rv.insert(name.clone(), value.to_string()); }
+        }
+    }
+    rv
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Imported rows are checked against the schema like the input file,
+// empty values are missing fields.
+fn to_passport(row: HashMap<String, String>, index: usize, schema: &Schema) -> io::Result<Passport> {
+    let mut entries: HashMap<String, String> = row.into_iter().filter(|(_, v)| !v.is_empty()).collect();
+    for field in schema.fields.iter().filter(|f| f.kind == FieldType::Measure) {
+        let value = entries.remove(&format!("{}_value", field.name));
+        let unit = entries.remove(&format!("{}_unit", field.name)).unwrap_or_default();
+        if let Some(value) = value {
+            entries.insert(field.name.clone(), value + &unit);
+        }
+    }
+    Passport::from_hashmap(&entries, schema).map_err(|violations| {
+        let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        invalid(format!("passport {}: {}", index, violations.join(", ")))
+    })
+}
+
+pub fn write_csv<W: Write>(schema: &Schema, passports: &[&Passport], writer: W) -> io::Result<()> {
+    let columns = columns(schema);
+    let mut wr = csv::Writer::from_writer(writer);
+    wr.write_record(&columns)?;
+    for p in passports {
+        let row = to_row(p);
+        wr.write_record(columns.iter().map(|c| row.get(c).map_or("", |v| v.as_str())))?;
+    }
+    wr.flush()
+}
+
+pub fn read_csv<R: Read>(reader: R, schema: &Schema) -> io::Result<Vec<Passport>> {
+    let mut rd = csv::Reader::from_reader(reader);
+    let mut rv = Vec::new();
+    for (index, row) in rd.deserialize().enumerate() {
+        rv.push(to_passport(row?, index, schema)?);
+    }
+    Ok(rv)
+}
+
+pub fn write_json<W: Write>(passports: &[&Passport], writer: W) -> io::Result<()> {
+    let rows: Vec<BTreeMap<String, String>> = passports.iter().map(|&p| to_row(p)).collect();
+    serde_json::to_writer_pretty(writer, &rows)?;
+    Ok(())
+}
+
+pub fn read_json<R: Read>(reader: R, schema: &Schema) -> io::Result<Vec<Passport>> {
+    let rows: Vec<HashMap<String, String>> = serde_json::from_reader(reader)?;
+    rows.into_iter().enumerate().map(|(index, row)| to_passport(row, index, schema)).collect()
+}
+
+// Import from a file, the format is picked from its extension.
+pub fn read_file(path: &str, schema: &Schema) -> io::Result<Vec<Passport>> {
+    let file = File::open(path)?;
+    if path.ends_with(".json") {
+        read_json(file, schema)
+    } else {
+        read_csv(file, schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passport(schema: &Schema) -> Passport {
+        let entries = "pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#023a2f"
+            .split_whitespace()
+            .map(|pair| {
+                let key_val: Vec<&str> = pair.splitn(2, ':').collect();
+                (key_val[0].to_string(), key_val[1].to_string())
+            })
+            .collect();
+        Passport::from_hashmap(&entries, schema).unwrap()
+    }
+
+    #[test]
+    fn test_csv() {
+        let schema = Schema::default_rules();
+        let p = passport(&schema);
+        let mut out = Vec::new();
+        write_csv(&schema, &[&p], &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out.clone()).unwrap(),
+            "byr,iyr,eyr,hgt_value,hgt_unit,hcl,ecl,pid,cid\n1980,2012,2030,74,in,#023a2f,grn,087499704,\n",
+        );
+        assert_eq!(read_csv(out.as_slice(), &schema).unwrap(), vec![p]);
+    }
+
+    #[test]
+    fn test_json() {
+        let schema = Schema::default_rules();
+        let p = passport(&schema);
+        let mut out = Vec::new();
+        write_json(&[&p], &mut out).unwrap();
+        let text = String::from_utf8(out.clone()).unwrap();
+        assert!(text.contains("\"hcl\": \"#023a2f\""));
+        assert!(text.contains("\"hgt_value\": \"74\""));
+        assert!(text.contains("\"hgt_unit\": \"in\""));
+        assert!(!text.contains("cid"));
+        assert_eq!(read_json(out.as_slice(), &schema).unwrap(), vec![p]);
+    }
+
+    #[test]
+    fn test_custom_schema() {
+        let schema = Schema::from_string("byr optional int\nhcl required text\nhgt optional measure unit=cm:150..193\ncid ignored int").unwrap();
+        let mut out = Vec::new();
+        let csv = "byr,hcl,hgt_value,hgt_unit\n,red,,\n1990,blue,170,cm\n";
+        let passports = read_csv(csv.as_bytes(), &schema).unwrap();
+        let passports: Vec<&Passport> = passports.iter().collect();
+        write_csv(&schema, &passports, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), csv);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let schema = Schema::default_rules();
+        let csv = "byr,iyr,eyr,hgt_value,hgt_unit,hcl,ecl,pid,cid\n1980,2012,2030,74,in,023a2f,grn,087499704,\n";
+        assert!(read_csv(csv.as_bytes(), &schema).is_err());
+        assert!(read_json("[{\"byr\": 1980}]".as_bytes(), &schema).is_err());
+        assert!(read_json("[{\"byr\": \"1980\"}]".as_bytes(), &schema).is_err());
+    }
+}
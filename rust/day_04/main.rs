@@ -1,195 +1,222 @@
-use std::{io, env};
+use std::{io, env, fmt};
 use std::fs::File;
 use std::io::{BufReader, BufRead};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-#[derive(Debug)]
+mod export;
+mod schema;
+
+use schema::{FieldType, Presence, Schema, Violation};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Height {
+    value: u32,
+    unit: String,
+}
+
+// A field value, converted according to the type the schema gives the field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Int(i64),
+    Hex { value: u32, digits: usize },  // digits kept to write it back as given
+    Measure(Height),
+    Text(String),  // enum and text fields
+}
+
+// Written back the way it is in the input file.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Hex { value, digits } => write!(f, "#{:0width$x}", value, width = digits),
+            Value::Measure(h) => write!(f, "{}{}", h.value, h.unit),
+            Value::Text(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+// The fields the schema accepted, by name. Optional fields which were not
+// given and ignored fields are left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Passport {
-    byr: u32,
-    iyr: u32,
-    eyr: u32,
-    hgt: String,
-    hcl: u32,
-    ecl: String,
-    pid: u64,
-    cid: u64,
+    fields: BTreeMap<String, Value>,
 }
 
-fn valid_passport_data(entries: &HashMap<String, String>) -> bool {
-    let need_keys = vec!("byr", "iyr", "eyr", "hgt", "hcl", "ecl", "pid");
-    need_keys.iter().filter(|&k| !entries.contains_key(&k.to_string())).count() == 0
+// One blank line separated record of the input file.
+#[derive(Debug, Clone)]
+struct Record {
+    index: usize,
+    lines: (usize, usize),  // first and last line, 1 based
+    entries: HashMap<String, String>,
 }
 
-fn read_passport_data(path: &str) -> io::Result<Vec<HashMap<String, String>>> {
+// What became of a record once checked against the schema.
+#[derive(Debug)]
+struct PassportCheck {
+    index: usize,
+    lines: (usize, usize),
+    passport: Result<Passport, Vec<Violation>>,
+}
+
+fn read_records(path: &str) -> io::Result<Vec<Record>> {
     let file = File::open(path)?;
     let br = BufReader::new(file);
     let mut rv = Vec::new();
     let mut data_store: HashMap<String, String> = HashMap::new();  // data is on multi lines
+    let mut lines = (0, 0);
 
-    for line in br.lines() {
+    for (idx, line) in br.lines().enumerate() {
         let line = line?;
         let entries: Vec<&str> = line.split_whitespace().collect();
-        if entries.len() == 0 {
+        if entries.is_empty() {
             // empty line between passports
-            if !valid_passport_data(&data_store) {
-                eprintln!("Cannot create passport from {:?}", data_store);
-            } else {
-                rv.push(data_store.clone());
+            if !data_store.is_empty() {
+                rv.push(Record { index: rv.len(), lines, entries: data_store.clone() });
             }
             data_store.clear();
         } else {
             // we have data to parse
+            if data_store.is_empty() {
+                lines.0 = idx + 1;
+            }
+            lines.1 = idx + 1;
             for pair in entries {
-                let key_val: Vec<&str> = pair.split(":").collect();
-                data_store.insert(key_val[0].to_string(), key_val[1].to_string());
+                let key_val: Vec<&str> = pair.splitn(2, ':').collect();
+                let value = key_val.get(1).unwrap_or(&"");
+                data_store.insert(key_val[0].to_string(), value.to_string());
             }
         }
     }
     if !data_store.is_empty() {
-        if !valid_passport_data(&data_store) {
-            eprintln!("Cannot create passport from {:?}", data_store);
-        } else {
-            rv.push(data_store);
-        }
+        rv.push(Record { index: rv.len(), lines, entries: data_store });
     }
     Ok(rv)
 }
 
-fn field_to_int(entries: &HashMap<String, String>, field: &str) -> Option<u32> {
-    let value = &entries[field];
-    match value.parse() {
-        Ok(v) => Some(v),
-        Err(_e) => {
-            eprintln!("{} invalid {}: {}", entries["pid"], field, value);
-            None
-        },
-    }
+// Records which have at least all the required fields.
+fn complete_records<'a>(records: &'a [Record], schema: &Schema) -> Vec<&'a Record> {
+    records.iter()
+        .filter(|r| schema.has_required(&r.entries))
+        .collect()
 }
 
-impl Passport {
-    fn from_hashmap(entries: &HashMap<String, String>) -> Option<Passport> {
-        let pid_str = &entries["pid"];
-        if pid_str.len() != 9 {
-            eprintln!("Invalid pid {}", pid_str);
-            return None;
-        }
-        let pid = match pid_str.parse() {
-            Ok(v) => v,
-            Err(_e) => {
-                eprintln!("Invalid pid {}", pid_str);
-                return None;
-            }
-        };
-
-        let byr = field_to_int(&entries, &"byr")?;
-        if byr < 1920 || byr > 2002 {
-            eprintln!("{} invalid birth year {}", pid_str, byr);
-            return None;
-        }
+fn to_height(value: &str) -> Option<Height> {
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    Some(Height { value: number.parse().ok()?, unit: unit.to_string() })
+}
 
-        let iyr = field_to_int(&entries, &"iyr")?;
-        if iyr < 2010 || iyr > 2020 {
-            eprintln!("{} invalid issue year {}", pid_str, iyr);
-            return None;
-        }
+fn convert(kind: &FieldType, value: &str) -> Option<Value> {
+    match kind {
+        FieldType::Int => value.parse().ok().map(Value::Int),
+        FieldType::Hex => value.strip_prefix('#')
+            .and_then(|v| Some(Value::Hex { value: u32::from_str_radix(v, 16).ok()?, digits: v.len() })),
+        FieldType::Measure => to_height(value).map(Value::Measure),
+        FieldType::Enum(_) | FieldType::Text => Some(Value::Text(value.to_string())),
+    }
+}
 
-        let eyr = field_to_int(&entries, &"eyr")?;
-        if eyr < 2020 || eyr > 2030 {
-            eprintln!("{} invalid expiration year {}", pid_str, eyr);
-            return None;
-        }
+impl Passport {
+    // The schema decides what is valid and the type of every field it keeps.
+    fn from_hashmap(entries: &HashMap<String, String>, schema: &Schema) -> Result<Passport, Vec<Violation>> {
+        schema.validate(entries)?;
 
-        let hgt_str = &entries["hgt"];
-        let hgt = match hgt_str
-                    .replace("cm", "")
-                    .replace("in", "").parse::<u32>() {
-            Ok(v) => v,
-            Err(_e) => {
-                eprintln!("{} invalid hgt {}", pid_str, hgt_str);
-                return None;
-            }
-        };
-        if hgt_str.ends_with("cm") {
-            if hgt < 150 || hgt > 193 {
-                eprintln!("{} invalid hgt in cm {}", pid_str, hgt);
-                return None;
-            }
-        } else if hgt_str.ends_with("in") {
-            if hgt < 59 || hgt > 76 {
-                eprintln!("{} invalid hgt in in {}", pid_str, hgt);
-                return None;
+        let mut fields = BTreeMap::new();
+        let mut violations = Vec::new();
+        for rule in schema.fields.iter().filter(|r| r.presence != Presence::Ignored) {
+            if let Some(value) = entries.get(&rule.name) {
+                match convert(&rule.kind, value) {
+                    Some(v) => { fields.insert(rule.name.clone(), v); }
+                    None => violations.push(Violation { field: rule.name.clone(), rule: "convert", value: Some(value.clone()) }),
+                }
             }
-        } else {
-            eprintln!("{} no unit in hgt {}", pid_str, hgt_str);
-            return None;
         }
-
-        let hcl_str = &entries["hcl"];
-        if !hcl_str.starts_with("#") {
-            eprintln!("{} invalid hair colour {}", pid_str, hcl_str);
-            return None;
-        }
-        let hcl = match u32::from_str_radix(&hcl_str.replace("#", ""), 16) {
-            Ok(v) => v,
-            Err(_e) => {
-                eprintln!("{} invalid hcl {}", pid_str, hcl_str);
-                return None;
-            }
-        };
-
-        let valid_eyes = vec!(
-            "amb", "blu", "brn", "gry", "grn", "hzl", "oth"
-        );
-        let ecl = &entries["ecl"];
-        // contains does not work with &String :(
-        if !valid_eyes.iter().any(|x| x.to_string() == *ecl) {
-            eprintln!("{} invalid eye colour {}", pid_str, ecl);
-            return None;
+        if !violations.is_empty() {
+            return Err(violations);
         }
+        Ok(Passport { fields })
+    }
 
-        // ignore cid to pass security
-        let cid_str = match entries.get("cid") {
-            Some(v) => v.clone(),
-            None => "0".to_string(),
-        };
-        let cid = match cid_str.parse::<u64>() {
-            Ok(v) => v,
-            Err(_e) => {
-                eprintln!("{} invalid cid {}", pid_str, cid_str);
-                return None;
-            },
-        };
-
-        Some(Passport {
-            byr,
-            iyr,
-            eyr,
-            hgt: hgt_str.clone(),
-            hcl,
-            ecl: ecl.clone(),
-            pid,
-            cid,
-        })
+    fn from_passport_data<'a>(passport_data: impl IntoIterator<Item = &'a Record>, schema: &Schema) -> Vec<PassportCheck> {
+        passport_data.into_iter()
+            .map(|record| PassportCheck {
+                index: record.index,
+                lines: record.lines,
+                passport: Self::from_hashmap(&record.entries, schema),
+            })
+            .collect()
     }
+}
 
-    fn from_passport_data(passport_data: Vec<HashMap<String, String>>) -> Vec<Passport> {
-        let mut rv = Vec::new();
-        for passport_entry in passport_data {
-            if let Some(passport) = Self::from_hashmap(&passport_entry) {
-                rv.push(passport);
+// Number of failures for each broken rule, as field.rule.
+fn summary(checks: &[PassportCheck]) -> BTreeMap<String, usize> {
+    let mut rv = BTreeMap::new();
+    for check in checks {
+        if let Err(violations) = &check.passport {
+            for v in violations {
+                *rv.entry(format!("{}.{}", v.field, v.rule)).or_insert(0) += 1;
             }
         }
-        rv
     }
+    rv
 }
 
 fn main() {
-    let path = env::args().nth(1).expect("please supply a path");
-    let passport_data = read_passport_data(&path).expect("no content");
-    println!("Found {} passports with all fields", passport_data.len());
+    let args: Vec<String> = env::args().skip(1).collect();
+    let show_summary = args.iter().any(|a| a == "--summary");
+    let show_report = args.iter().any(|a| a == "--report");
+    let option = |name: &str| args.iter()
+        .find_map(|a| a.strip_prefix(&format!("--{}=", name)).map(|v| v.to_string()));
+    let csv_path = option("csv");
+    let json_path = option("json");
+    let args: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
 
-    let valid_passports = Passport::from_passport_data(passport_data);
+    let path = args.first().expect("please supply a path");
+    let schema = match args.get(1) {
+        Some(schema_path) => Schema::read(schema_path).expect("invalid schema"),
+        None => Schema::default_rules(),
+    };
+    if path.ends_with(".csv") || path.ends_with(".json") {
+        // previously exported passports, checked again against the schema
+        let passports = export::read_file(path, &schema).expect("cannot import passports");
+        println!("Imported {} passports", passports.len());
+        return;
+    }
+
+    let records = read_records(path).expect("no content");
+    println!("Found {} passports with all fields", complete_records(&records, &schema).len());
+
+    // check every record so the report also covers the ones missing fields
+    let checks = Passport::from_passport_data(&records, &schema);
+    let valid_passports: Vec<&Passport> = checks.iter()
+        .filter_map(|c| c.passport.as_ref().ok())
+        .collect();
     println!("Found {} passports with valid fields", valid_passports.len());
+
+    if show_report {
+        for check in &checks {
+            if let Err(violations) = &check.passport {
+                let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                println!("passport {} (lines {}-{}): {}",
+                    check.index, check.lines.0, check.lines.1, violations.join(", "));
+            }
+        }
+    }
+    if show_summary {
+        for (rule, count) in summary(&checks) {
+            println!("{:>16} {}", rule, count);
+        }
+    }
+    if let Some(csv_path) = csv_path {
+        let file = File::create(&csv_path).expect("cannot create csv");
+        export::write_csv(&schema, &valid_passports, file).expect("cannot write csv");
+        println!("Wrote {}", csv_path);
+    }
+    if let Some(json_path) = json_path {
+        let file = File::create(&json_path).expect("cannot create json");
+        export::write_json(&valid_passports, file).expect("cannot write json");
+        println!("Wrote {}", json_path);
+    }
 }
 
 #[cfg(test)]
@@ -200,7 +227,7 @@ mod tests {
     fn hash_from_string(value: &str) -> HashMap<String, String> {
         let mut data_store: HashMap<String, String> = HashMap::new();  // data is on multi lines
         for pair in value.split_whitespace() {
-            let key_val: Vec<&str> = pair.split(":").collect();
+            let key_val: Vec<&str> = pair.split(':').collect();
             data_store.insert(key_val[0].to_string(), key_val[1].to_string());
         }
         data_store
@@ -208,54 +235,141 @@ mod tests {
 
     #[rstest()]
     fn test_read_multiple_lines() {
-        let data = read_passport_data(&"day_04/test_1.txt");
-        assert!(data.is_ok());
-        assert_eq!(data.unwrap().len(), 2);
+        let records = read_records("day_04/test_1.txt").expect("no content");
+        assert_eq!(complete_records(&records, &Schema::default_rules()).len(), 2);
     }
 
     #[rstest(content,
-    case(&"eyr:1972 cid:100 hcl:#18171d ecl:amb hgt:170 iyr:2018 byr:1926"),
-    case(&"iyr:2019 hcl:#602927 eyr:1967 hgt:170cm pid:012533040 byr:1946"),
+    case("eyr:1972 cid:100 hcl:#18171d ecl:amb hgt:170 iyr:2018 byr:1926"),
+    case("iyr:2019 hcl:#602927 eyr:1967 hgt:170cm pid:012533040 byr:1946"),
     )]
     fn test_missing_entries_passport(content: &str) {
         println!("Checking {}", content);
-        assert!(!valid_passport_data(&hash_from_string(content)));
+        assert!(!Schema::default_rules().has_required(&hash_from_string(content)));
     }
 
     #[rstest(content,
-    case(&"eyr:1972 cid:100 hcl:#18171d ecl:amb hgt:170 pid:186cm iyr:2018 byr:1926"),
-    case(&"iyr:2019 hcl:#602927 eyr:1967 hgt:170cm ecl:grn pid:012533040 byr:1946"),
-    case(&"hcl:dab227 iyr:2012 ecl:brn hgt:182cm pid:021572410 eyr:2020 byr:1992 cid:277"),
-    case(&"hgt:59cm ecl:zzz eyr:2038 hcl:74454a iyr:2023 pid:3556412378 byr:2007"),
+    case("eyr:1972 cid:100 hcl:#18171d ecl:amb hgt:170 pid:186cm iyr:2018 byr:1926"),
+    case("iyr:2019 hcl:#602927 eyr:1967 hgt:170cm ecl:grn pid:012533040 byr:1946"),
+    case("hcl:dab227 iyr:2012 ecl:brn hgt:182cm pid:021572410 eyr:2020 byr:1992 cid:277"),
+    case("hgt:59cm ecl:zzz eyr:2038 hcl:74454a iyr:2023 pid:3556412378 byr:2007"),
     )]
     fn test_invalid_passport(content: &str) {
         println!("Checking {}", content);
-        let p = Passport::from_hashmap(&hash_from_string(content));
-        assert!(p.is_none());
+        let p = Passport::from_hashmap(&hash_from_string(content), &Schema::default_rules());
+        assert!(p.is_err());
     }
 
     #[rstest(content,
-    case(&"pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f"),
-    case(&"eyr:2029 ecl:blu cid:129 byr:1989 iyr:2014 pid:896056539 hcl:#a97842 hgt:165cm"),
-    case(&"hcl:#888785 hgt:164cm byr:2001 iyr:2015 cid:88 pid:545766238 ecl:hzl eyr:2022"),
-    case(&"iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719"),
+    case("pid:087499704 hgt:74in ecl:grn iyr:2012 eyr:2030 byr:1980 hcl:#623a2f"),
+    case("eyr:2029 ecl:blu cid:129 byr:1989 iyr:2014 pid:896056539 hcl:#a97842 hgt:165cm"),
+    case("hcl:#888785 hgt:164cm byr:2001 iyr:2015 cid:88 pid:545766238 ecl:hzl eyr:2022"),
+    case("iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719"),
     )]
     fn test_valid_passport(content: &str) {
         println!("Checking {}", content);
-        let p = Passport::from_hashmap(&hash_from_string(content));
-        assert!(p.is_some());
+        let p = Passport::from_hashmap(&hash_from_string(content), &Schema::default_rules());
+        assert!(p.is_ok());
+    }
+
+    #[rstest(cid_rule, exp,
+    case("cid required int", false),
+    case("cid optional int", false),
+    case("cid ignored int", true),
+    )]
+    fn test_cid_is_configurable(cid_rule: &str, exp: bool) {
+        let schema = Schema::from_string(&format!("pid required int regex=^[0-9]{{9}}$\n{}", cid_rule)).unwrap();
+        let entries = hash_from_string("pid:087499704 cid:abc");
+        assert_eq!(schema.validate(&entries).is_ok(), exp);
     }
 
     #[rstest()]
     fn test_q1() {
-        let passport_data = read_passport_data(&"day_04/input.txt").expect("no content");
-        assert_eq!(passport_data.len(), 182);
+        let records = read_records("day_04/input.txt").expect("no content");
+        assert_eq!(complete_records(&records, &Schema::default_rules()).len(), 182);
     }
 
     #[rstest()]
     fn test_q2() {
-        let passport_data = read_passport_data(&"day_04/input.txt").expect("no content");
-        let valid_passports = Passport::from_passport_data(passport_data);
-        assert_eq!(valid_passports.len(), 109);
+        let schema = Schema::default_rules();
+        let records = read_records("day_04/input.txt").expect("no content");
+        let checks = Passport::from_passport_data(complete_records(&records, &schema), &schema);
+        assert_eq!(checks.iter().filter(|c| c.passport.is_ok()).count(), 109);
+    }
+
+    #[rstest(value, exp,
+    case("74in", Height { value: 74, unit: "in".to_string() }),
+    case("165cm", Height { value: 165, unit: "cm".to_string() }),
+    case("165", Height { value: 165, unit: String::new() }),
+    )]
+    fn test_height(value: &str, exp: Height) {
+        assert_eq!(to_height(value), Some(exp));
+    }
+
+    #[rstest()]
+    fn test_schema_drives_passport() {
+        let schema = Schema::from_string("byr optional int range=1920..2002\nhcl required text\nhgt required measure unit=cm:150..193\ncid ignored int").unwrap();
+        let p = Passport::from_hashmap(&hash_from_string("hcl:red hgt:170cm cid:abc"), &schema).unwrap();
+        let mut exp = BTreeMap::new();
+        exp.insert("hcl".to_string(), Value::Text("red".to_string()));
+        exp.insert("hgt".to_string(), Value::Measure(Height { value: 170, unit: "cm".to_string() }));
+        assert_eq!(p, Passport { fields: exp.clone() });
+
+        let p = Passport::from_hashmap(&hash_from_string("byr:1990 hcl:red hgt:170cm"), &schema).unwrap();
+        exp.insert("byr".to_string(), Value::Int(1990));
+        assert_eq!(p, Passport { fields: exp });
+    }
+
+    #[rstest(kind, value, exp,
+    case(FieldType::Int, "087499704", Some(Value::Int(87499704))),
+    case(FieldType::Int, "abc", None),
+    case(FieldType::Hex, "#623a2f", Some(Value::Hex { value: 0x623a2f, digits: 6 })),
+    case(FieldType::Hex, "#0abc", Some(Value::Hex { value: 0xabc, digits: 4 })),
+    case(FieldType::Hex, "#fffffffff", None),
+    case(FieldType::Text, "#623a2f", Some(Value::Text("#623a2f".to_string()))),
+    )]
+    fn test_convert(kind: FieldType, value: &str, exp: Option<Value>) {
+        assert_eq!(convert(&kind, value), exp);
+    }
+
+    #[rstest(value,
+    case("#abc"),
+    case("#000abc"),
+    case("#623a2f"),
+    case("#0"),
+    )]
+    fn test_hex_round_trip(value: &str) {
+        assert_eq!(convert(&FieldType::Hex, value).unwrap().to_string(), value);
+    }
+
+    #[rstest()]
+    fn test_records_keep_their_lines() {
+        let records = read_records("day_04/test_1.txt").expect("no content");
+        let lines: Vec<(usize, (usize, usize))> = records.iter().map(|r| (r.index, r.lines)).collect();
+        assert_eq!(lines, vec![(0, (1, 2)), (1, (4, 5)), (2, (7, 10)), (3, (12, 13))]);
+    }
+
+    #[rstest()]
+    fn test_report_and_summary() {
+        let schema = Schema::default_rules();
+        let checks = Passport::from_passport_data(&read_records("day_04/test_1.txt").unwrap(), &schema);
+        let invalid: Vec<(usize, Vec<String>)> = checks.iter()
+            .filter_map(|c| c.passport.as_ref().err().map(|v| (c.index, v.iter().map(|v| v.to_string()).collect())))
+            .collect();
+        assert_eq!(invalid, vec![
+            (1, vec!["hgt.missing".to_string()]),
+            (3, vec!["byr.missing".to_string()]),
+        ]);
+
+        let counts: Vec<(String, usize)> = summary(&checks).into_iter().collect();
+        assert_eq!(counts, vec![("byr.missing".to_string(), 1), ("hgt.missing".to_string(), 1)]);
+    }
+
+    #[rstest()]
+    fn test_every_violation_is_reported() {
+        let entries = hash_from_string("hgt:59cm ecl:zzz eyr:2038 hcl:74454a iyr:2023 pid:3556412378 byr:2007");
+        let violations = Passport::from_hashmap(&entries, &Schema::default_rules()).unwrap_err();
+        let rules: Vec<String> = violations.iter().map(|v| format!("{}.{}", v.field, v.rule)).collect();
+        assert_eq!(rules, vec!["byr.range", "iyr.range", "eyr.range", "hgt.range", "hcl.regex", "ecl.values", "pid.regex"]);
     }
 }
@@ -0,0 +1,332 @@
+use std::{io, fmt};
+use std::collections::HashMap;
+use std::fs;
+use regex::Regex;
+
+// Rules used when no schema file is given.
+static DEFAULT_SCHEMA: &str = include_str!("schema.txt");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Presence {
+    Required,
+    Optional,
+    Ignored,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldType {
+    Int,
+    Hex,  // #-prefixed hexadecimal number
+    Measure,  // number followed by a unit
+    Enum(Vec<String>),
+    Text,
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldRule {
+    pub name: String,
+    pub presence: Presence,
+    pub kind: FieldType,
+    range: Option<(i64, i64)>,
+    units: Vec<(String, i64, i64)>,
+    regex: Option<Regex>,
+}
+
+// Why a field was refused: the field, the rule it broke and the offending value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub field: String,
+    pub rule: &'static str,
+    pub value: Option<String>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            Some(v) => write!(f, "{}.{}: {:?}", self.field, self.rule, v),
+            None => write!(f, "{}.{}", self.field, self.rule),
+        }
+    }
+}
+
+fn parse_range(value: &str) -> Option<(i64, i64)> {
+    let bounds: Vec<&str> = value.split("..").collect();
+    if bounds.len() != 2 {
+        return None;
+    }
+    Some((bounds[0].parse().ok()?, bounds[1].parse().ok()?))
+}
+
+fn invalid(line_no: usize, msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("schema line {}: {}", line_no, msg))
+}
+
+impl FieldRule {
+    fn from_string(line: &str, line_no: usize) -> io::Result<FieldRule> {
+        let entries: Vec<&str> = line.split_whitespace().collect();
+        if entries.len() < 3 {
+            return Err(invalid(line_no, "expected <name> <presence> <type>"));
+        }
+
+        let presence = match entries[1] {
+            "required" => Presence::Required,
+            "optional" => Presence::Optional,
+            "ignored" => Presence::Ignored,
+            other => return Err(invalid(line_no, &format!("unknown presence {}", other))),
+        };
+
+        let mut rule = FieldRule {
+            name: entries[0].to_string(),
+            presence,
+            kind: FieldType::Text,
+            range: None,
+            units: Vec::new(),
+            regex: None,
+        };
+        let mut values = None;
+
+        for option in &entries[3..] {
+            let key_val: Vec<&str> = option.splitn(2, '=').collect();
+            if key_val.len() != 2 {
+                return Err(invalid(line_no, &format!("option {} is not key=value", option)));
+            }
+            match key_val[0] {
+                "range" => {
+                    rule.range = Some(parse_range(key_val[1])
+                        .ok_or_else(|| invalid(line_no, &format!("bad range {}", key_val[1])))?);
+                }
+                "unit" => {
+                    let unit_range: Vec<&str> = key_val[1].splitn(2, ':').collect();
+                    let range = unit_range.get(1)
+                        .and_then(|r| parse_range(r))
+                        .ok_or_else(|| invalid(line_no, &format!("bad unit {}", key_val[1])))?;
+                    rule.units.push((unit_range[0].to_string(), range.0, range.1));
+                }
+                "values" => {
+                    values = Some(key_val[1].split(',').map(|v| v.to_string()).collect());
+                }
+                "regex" => {
+                    let re = Regex::new(key_val[1])
+                        .map_err(|e| invalid(line_no, &format!("bad regex {}: {}", key_val[1], e)))?;
+                    rule.regex = Some(re);
+                }
+                other => return Err(invalid(line_no, &format!("unknown option {}", other))),
+            }
+        }
+
+        rule.kind = match entries[2] {
+            "int" => FieldType::Int,
+            "hex" => FieldType::Hex,
+            "measure" => {
+                if rule.units.is_empty() {
+                    return Err(invalid(line_no, "measure needs at least one unit"));
+                }
+                FieldType::Measure
+            }
+            "enum" => FieldType::Enum(values.ok_or_else(|| invalid(line_no, "enum needs values"))?),
+            "text" => FieldType::Text,
+            other => return Err(invalid(line_no, &format!("unknown type {}", other))),
+        };
+        Ok(rule)
+    }
+
+    fn check_range(&self, value: i64, range: Option<(i64, i64)>) -> Result<(), Violation> {
+        match range {
+            Some((min, max)) if value < min || value > max => Err(self.violation("range", Some(value.to_string()))),
+            _ => Ok(()),
+        }
+    }
+
+    fn violation(&self, rule: &'static str, value: Option<String>) -> Violation {
+        Violation { field: self.name.clone(), rule, value }
+    }
+
+    pub fn validate(&self, value: Option<&String>) -> Result<(), Violation> {
+        if self.presence == Presence::Ignored {
+            return Ok(());
+        }
+        let value = match value {
+            Some(v) => v,
+            None if self.presence == Presence::Required => return Err(self.violation("missing", None)),
+            None => return Ok(()),
+        };
+        let bad = |rule| Err(self.violation(rule, Some(value.clone())));
+
+        if let Some(re) = &self.regex {
+            if !re.is_match(value) {
+                return bad("regex");
+            }
+        }
+
+        match &self.kind {
+            FieldType::Int => match value.parse() {
+                Ok(v) => self.check_range(v, self.range),
+                Err(_) => bad("type"),
+            },
+            FieldType::Hex => {
+                if !value.starts_with('#') {
+                    return bad("type");
+                }
+                match i64::from_str_radix(&value[1..], 16) {
+                    Ok(v) => self.check_range(v, self.range),
+                    Err(_) => bad("type"),
+                }
+            }
+            FieldType::Measure => {
+                let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+                let (number, unit) = value.split_at(split);
+                let number = match number.parse() {
+                    Ok(v) => v,
+                    Err(_) => return bad("type"),
+                };
+                match self.units.iter().find(|(u, _, _)| u == unit) {
+                    Some((_, min, max)) => self.check_range(number, Some((*min, *max))),
+                    None => bad("unit"),
+                }
+            }
+            FieldType::Enum(values) => {
+                if values.contains(value) {
+                    Ok(())
+                } else {
+                    bad("values")
+                }
+            }
+            FieldType::Text => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Schema {
+    pub fields: Vec<FieldRule>,
+}
+
+impl Schema {
+    pub fn from_string(content: &str) -> io::Result<Schema> {
+        let mut fields = Vec::new();
+        for (idx, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            fields.push(FieldRule::from_string(line, idx + 1)?);
+        }
+        Ok(Schema { fields })
+    }
+
+    pub fn read(path: &str) -> io::Result<Schema> {
+        Self::from_string(&fs::read_to_string(path)?)
+    }
+
+    pub fn default_rules() -> Schema {
+        Self::from_string(DEFAULT_SCHEMA).expect("default schema is valid")
+    }
+
+    #[cfg(test)]
+    pub fn presence(&self, field: &str) -> Presence {
+        self.fields.iter()
+            .find(|f| f.name == field)
+            .map_or(Presence::Ignored, |f| f.presence)
+    }
+
+    // Only check that every required field is there.
+    pub fn has_required(&self, entries: &HashMap<String, String>) -> bool {
+        self.fields.iter()
+            .filter(|f| f.presence == Presence::Required)
+            .all(|f| entries.contains_key(&f.name))
+    }
+
+    // Check every field, reporting the first rule each invalid field breaks.
+    pub fn validate(&self, entries: &HashMap<String, String>) -> Result<(), Vec<Violation>> {
+        let violations: Vec<Violation> = self.fields.iter()
+            .filter_map(|field| field.validate(entries.get(&field.name)).err())
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn check(line: &str, value: Option<&str>) -> Result<(), &'static str> {
+        let rule = FieldRule::from_string(line, 1).expect("valid rule");
+        rule.validate(value.map(|v| v.to_string()).as_ref()).map_err(|v| v.rule)
+    }
+
+    #[rstest(line, value, exp,
+    case("byr required int range=1920..2002", Some("2002"), Ok(())),
+    case("byr required int range=1920..2002", Some("2003"), Err("range")),
+    case("byr required int range=1920..2002", Some("abc"), Err("type")),
+    case("byr required int range=1920..2002", None, Err("missing")),
+    case("byr optional int range=1920..2002", None, Ok(())),
+    case("byr ignored int range=1920..2002", Some("abc"), Ok(())),
+    case("pid required int regex=^[0-9]{9}$", Some("000000001"), Ok(())),
+    case("pid required int regex=^[0-9]{9}$", Some("0123456789"), Err("regex")),
+    case("hgt required measure unit=cm:150..193 unit=in:59..76", Some("60in"), Ok(())),
+    case("hgt required measure unit=cm:150..193 unit=in:59..76", Some("190cm"), Ok(())),
+    case("hgt required measure unit=cm:150..193 unit=in:59..76", Some("190in"), Err("range")),
+    case("hgt required measure unit=cm:150..193 unit=in:59..76", Some("190"), Err("unit")),
+    case("hgt required measure unit=cm:150..193 unit=in:59..76", Some("cm"), Err("type")),
+    case("hcl required hex", Some("#123abc"), Ok(())),
+    case("hcl required hex", Some("123abc"), Err("type")),
+    case("hcl required hex", Some("#123abz"), Err("type")),
+    case("ecl required enum values=brn,wat", Some("wat"), Ok(())),
+    case("ecl required enum values=brn,wat", Some("gry"), Err("values")),
+    case("txt required text", Some("anything"), Ok(())),
+    )]
+    fn test_field_rule(line: &str, value: Option<&str>, exp: Result<(), &'static str>) {
+        assert_eq!(check(line, value), exp);
+    }
+
+    #[rstest(line,
+    case("byr"),
+    case("byr maybe int"),
+    case("byr required float"),
+    case("byr required int range=1920"),
+    case("byr required int regex=(abc"),
+    case("byr required int colour=red"),
+    case("hgt required measure"),
+    case("ecl required enum"),
+    )]
+    fn test_invalid_rule(line: &str) {
+        assert!(FieldRule::from_string(line, 1).is_err());
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let schema = Schema::default_rules();
+        assert_eq!(schema.fields.len(), 8);
+        assert_eq!(schema.presence("byr"), Presence::Required);
+        assert_eq!(schema.presence("cid"), Presence::Optional);
+        assert_eq!(schema.presence("xyz"), Presence::Ignored);
+    }
+
+    #[test]
+    fn test_validate_reports_every_field() {
+        let schema = Schema::default_rules();
+        let mut entries = HashMap::new();
+        entries.insert("byr".to_string(), "1900".to_string());
+        entries.insert("hgt".to_string(), "190in".to_string());
+        entries.insert("ecl".to_string(), "brn".to_string());
+
+        let rv: Vec<String> = schema.validate(&entries)
+            .unwrap_err()
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(rv, vec![
+            "byr.range: \"1900\"",
+            "iyr.missing",
+            "eyr.missing",
+            "hgt.range: \"190\"",
+            "hcl.missing",
+            "pid.missing",
+        ]);
+    }
+}
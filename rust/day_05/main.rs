@@ -1,16 +1,15 @@
 use std::{io, env};
 use std::fs::File;
 use std::io::{BufReader, BufRead};
-use std::collections::HashSet;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Seat {
     row: usize,
-    seat: usize,  // 0=Leftmost, 7=rightmost
+    seat: usize,  // 0=Leftmost
 }
 
 fn update_range(range: (usize, usize), upper: bool) -> (usize, usize) {
-    let half_size = ((range.1 - range.0) + 1) / 2;
+    let half_size = (range.1 - range.0).div_ceil(2);
     if upper {
         (range.0, range.1 - half_size)
     } else {
@@ -18,82 +17,181 @@ fn update_range(range: (usize, usize), upper: bool) -> (usize, usize) {
     }
 }
 
-impl Seat {
-    fn from_string(val: &String) -> Seat {
-        let mut row_range = (0, 127);
-        let mut seat_range = (0, 7);
-
-        // eprintln!("init ranges row={:?} seat={:?}", row_range, seat_range);
-        for c in val.chars() {
-            if c == 'F' || c == 'B' {
-                row_range = update_range(row_range, c == 'F');
-            } else if c == 'R' || c == 'L' {
-                seat_range = update_range(seat_range, c == 'L');
+// Shape of the plane. Both sizes have to be powers of 2 so each letter of a
+// boarding pass halves the range of possible rows or seats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Layout {
+    rows: usize,
+    columns: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout { rows: 128, columns: 8 }
+    }
+}
+
+impl Layout {
+    fn new(rows: usize, columns: usize) -> Result<Layout, String> {
+        if !rows.is_power_of_two() || !columns.is_power_of_two() {
+            return Err(format!("{}x{} is not a power of 2 layout", rows, columns));
+        }
+        Ok(Layout { rows, columns })
+    }
+
+    fn row_letters(&self) -> usize {
+        self.rows.trailing_zeros() as usize
+    }
+
+    fn column_letters(&self) -> usize {
+        self.columns.trailing_zeros() as usize
+    }
+
+    fn seat_count(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    fn id(&self, seat: &Seat) -> usize {
+        seat.row * self.columns + seat.seat
+    }
+
+    fn seat(&self, id: usize) -> Result<Seat, String> {
+        if id >= self.seat_count() {
+            return Err(format!("seat id {} is outside of the plane", id));
+        }
+        Ok(Seat { row: id / self.columns, seat: id % self.columns })
+    }
+
+    fn decode(&self, code: &str) -> Result<Seat, String> {
+        let expected = self.row_letters() + self.column_letters();
+        if code.chars().count() != expected {
+            return Err(format!("{:?} should have {} letters", code, expected));
+        }
+
+        let mut row_range = (0, self.rows - 1);
+        let mut seat_range = (0, self.columns - 1);
+        for (idx, c) in code.chars().enumerate() {
+            let is_row = idx < self.row_letters();
+            match (c, is_row) {
+                ('F', true) | ('B', true) => row_range = update_range(row_range, c == 'F'),
+                ('L', false) | ('R', false) => seat_range = update_range(seat_range, c == 'L'),
+                _ => {
+                    let allowed = if is_row { "F or B" } else { "L or R" };
+                    return Err(format!("{:?}: unexpected {:?} at {}, expected {}", code, c, idx, allowed));
+                }
             }
-            // eprintln!(" {} -> ranges row={:?} seat={:?}", c, row_range, seat_range);
         }
 
-        Seat{row:row_range.0, seat:seat_range.1}
+        Ok(Seat { row: row_range.0, seat: seat_range.0 })
+    }
+
+    fn encode(&self, seat: &Seat) -> Result<String, String> {
+        if seat.row >= self.rows || seat.seat >= self.columns {
+            return Err(format!("{:?} is outside of the plane", seat));
+        }
+        let letters = |value: usize, count: usize, low: char, high: char| -> String {
+            (0..count).rev()
+                .map(|bit| if value & (1 << bit) == 0 { low } else { high })
+                .collect()
+        };
+        Ok(letters(seat.row, self.row_letters(), 'F', 'B')
+            + &letters(seat.seat, self.column_letters(), 'L', 'R'))
     }
+}
 
-    fn read(path: &str) -> io::Result<Vec<Seat>> {
+impl Seat {
+    fn read(path: &str, layout: &Layout) -> io::Result<Vec<Seat>> {
         let file = File::open(path)?;
         let br = BufReader::new(file);
         let mut rv = Vec::new();
 
-        for line in br.lines() {
+        for (idx, line) in br.lines().enumerate() {
             let line = line?;
-
-            rv.push(Seat::from_string(&line));
+            let seat = layout.decode(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", idx + 1, e)))?;
+            rv.push(seat);
         }
         Ok(rv)
     }
+}
 
-    fn id(&self) -> usize {
-        self.row * 8 + self.seat
-    }
+#[derive(Debug)]
+struct Plane {
+    layout: Layout,
+    occupied: Vec<bool>,  // indexed by seat id
 }
 
-fn find_my_seat(seats: &[Seat]) -> Option<Seat> {
-    let seat_map :HashSet<usize> = seats.iter().map(|s| s.id()).collect();
+impl Plane {
+    fn new(layout: Layout, seats: &[Seat]) -> Plane {
+        let mut occupied = vec![false; layout.seat_count()];
+        for s in seats {
+            occupied[layout.id(s)] = true;
+        }
+        Plane { layout, occupied }
+    }
 
-    let max_seat = seat_map.iter().max().unwrap();
-    println!("Q1: Max id is {}", max_seat);
+    fn is_occupied(&self, id: usize) -> bool {
+        *self.occupied.get(id).unwrap_or(&false)
+    }
 
-    for i in 1 as usize..*max_seat {
-        if seat_map.contains(&i) {
-            continue;
-        }
-        // this is an empty seat but to be my seat id+1 and id-1 have to be taken
-        if seat_map.contains(&(i - 1)) && seat_map.contains(&(i + 1)) {
-            // eprintln!("Found possible seat id={}", i);
-            return Some(Seat{
-                row: i / 8,
-                seat: i % 8,
-            })
-        }
+    fn occupancy(&self) -> usize {
+        self.occupied.iter().filter(|&&o| o).count()
+    }
+
+    // Empty seats with both id-1 and id+1 taken, which is where my seat can be.
+    fn empty_with_neighbours(&self) -> Vec<Seat> {
+        (1..self.layout.seat_count())
+            .filter(|&id| !self.is_occupied(id) && self.is_occupied(id - 1) && self.is_occupied(id + 1))
+            .map(|id| self.layout.seat(id).unwrap())
+            .collect()
     }
 
-    None
+    // One line per row: # for taken seats and . for empty ones.
+    fn render(&self) -> String {
+        self.occupied
+            .chunks(self.layout.columns)
+            .enumerate()
+            .map(|(row, seats)| {
+                let seats: String = seats.iter().map(|&o| if o { '#' } else { '.' }).collect();
+                format!("{:>4} {}\n", row, seats)
+            })
+            .collect()
+    }
 }
 
 fn main() {
-    let path = env::args().nth(1).expect("please supply a path");
-    let contents = Seat::read(&path).expect("no content");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let show_map = args.iter().any(|a| a == "--map");
+    let args: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
 
-    println!("Loaded {} seats", contents.len());
-    let my_seat = if contents.len() > 0 {
-        find_my_seat(&contents)
-    } else {
-        None
+    let path = args.first().expect("please supply a path");
+    let layout = match (args.get(1), args.get(2)) {
+        (Some(rows), Some(columns)) => Layout::new(
+            rows.parse().expect("rows should be a number"),
+            columns.parse().expect("columns should be a number"),
+        ).expect("invalid layout"),
+        _ => Layout::default(),
     };
+    let contents = Seat::read(path, &layout).expect("no content");
+    let plane = Plane::new(layout, &contents);
 
-    if my_seat.is_some() {
-        let my_seat = my_seat.unwrap();
-        println!("Q2: My seat is {:?} id={}", my_seat, my_seat.id());
-    } else {
+    println!("Loaded {} seats, {} are taken", contents.len(), plane.occupancy());
+    if let Some(max_seat) = contents.iter().map(|s| layout.id(s)).max() {
+        println!("Q1: Max id is {}", max_seat);
+    }
+
+    let free = plane.empty_with_neighbours();
+    if free.is_empty() {
         println!("No free seat :(");
     }
+    for my_seat in free {
+        println!("Q2: My seat is {:?} id={} pass={}",
+            my_seat, layout.id(&my_seat), layout.encode(&my_seat).unwrap());
+    }
+
+    if show_map {
+        print!("{}", plane.render());
+    }
 }
 
 #[cfg(test)]
@@ -116,16 +214,77 @@ mod tests {
     }
 
     #[rstest(input, exp_row, exp_seat, exp_id,
-    case(&"FBFBBFFRLR", 44, 5, 357),
-    case(&"BFFFBBFRRR", 70, 7, 567),
-    case(&"FFFBBBFRRR", 14, 7, 119),
-    case(&"BBFFBBFRLL", 102, 4, 820),
+    case("FBFBBFFRLR", 44, 5, 357),
+    case("BFFFBBFRRR", 70, 7, 567),
+    case("FFFBBBFRRR", 14, 7, 119),
+    case("BBFFBBFRLL", 102, 4, 820),
     )]
     fn test_boarding_pass(input: &str, exp_row: usize, exp_seat: usize, exp_id: usize) {
-        let seat = Seat::from_string(&input.to_string());
+        let layout = Layout::default();
+        let seat = layout.decode(input).expect("valid pass");
         println!("Loaded {:?}", seat);
         assert_eq!(seat.row, exp_row);
         assert_eq!(seat.seat, exp_seat);
-        assert_eq!(seat.id(), exp_id);
+        assert_eq!(layout.id(&seat), exp_id);
+        assert_eq!(layout.seat(exp_id), Ok(seat));
+        assert_eq!(layout.encode(&seat).unwrap(), input);
+    }
+
+    #[rstest(rows, columns, input, exp_id,
+    case(4, 4, "BFRL", 10),
+    case(1, 8, "RRR", 7),
+    case(2, 1, "B", 1),
+    )]
+    fn test_other_layouts(rows: usize, columns: usize, input: &str, exp_id: usize) {
+        let layout = Layout::new(rows, columns).unwrap();
+        let seat = layout.decode(input).unwrap();
+        assert_eq!(layout.id(&seat), exp_id);
+        assert_eq!(layout.encode(&seat).unwrap(), input);
+    }
+
+    #[test]
+    fn test_round_trip_every_seat() {
+        let layout = Layout::default();
+        for id in 0..layout.seat_count() {
+            let seat = layout.seat(id).unwrap();
+            assert_eq!(layout.decode(&layout.encode(&seat).unwrap()), Ok(seat));
+        }
+    }
+
+    #[rstest(input,
+    case("FBFBBFFRL"),
+    case("FBFBBFFRLRR"),
+    case("FBFBBFFRLX"),
+    case("FBFBBFRRLR"),
+    case("LBFBBFFRLR"),
+    )]
+    fn test_malformed_pass(input: &str) {
+        assert!(Layout::default().decode(input).is_err());
+    }
+
+    #[test]
+    fn test_invalid_layout() {
+        assert!(Layout::new(100, 8).is_err());
+        assert!(Layout::default().seat(1024).is_err());
+        assert!(Layout::default().encode(&Seat { row: 1, seat: 8 }).is_err());
+    }
+
+    #[test]
+    fn test_plane() {
+        let layout = Layout::new(2, 4).unwrap();
+        let seats: Vec<Seat> = [0, 1, 3, 4, 6].iter().map(|&id| layout.seat(id).unwrap()).collect();
+        let plane = Plane::new(layout, &seats);
+        assert_eq!(plane.occupancy(), 5);
+        assert_eq!(plane.empty_with_neighbours(), vec![layout.seat(2).unwrap(), layout.seat(5).unwrap()]);
+        assert_eq!(plane.render(), "   0 ##.#\n   1 #.#.\n");
+    }
+
+    #[test]
+    fn test_input() {
+        let layout = Layout::default();
+        let seats = Seat::read("day_05/input.txt", &layout).unwrap();
+        let plane = Plane::new(layout, &seats);
+        let free: Vec<usize> = plane.empty_with_neighbours().iter().map(|s| layout.id(s)).collect();
+        assert_eq!(free.len(), 1);
     }
 }
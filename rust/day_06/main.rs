@@ -1,7 +1,14 @@
 use std::{io, env};
 use std::fs::File;
-use std::io::{BufReader, BufRead};
-use std::collections::HashMap;
+use std::io::{BufReader, BufRead, Lines};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Quorum {
+    AtLeast(usize),
+    Exactly(usize),
+    Everyone,
+}
 
 #[derive(Debug, Clone)]
 struct Group {
@@ -9,22 +16,26 @@ struct Group {
     answers: HashMap<char, usize>,  // question => nb answers
 }
 
-impl Group {
-    fn read(path: &str) -> io::Result<Vec<Group>> {
-        let file = File::open(path)?;
-        let br = BufReader::new(file);
-        let mut rv = Vec::new();
+// Lazily read groups, they are separated by empty lines.
+struct Groups<B> {
+    lines: Lines<B>,
+}
 
-        let mut current_group = Group {
-            size: 0,
-            answers: HashMap::new(),
-        };
-        for line in br.lines() {
-            let line = line?;
+impl<B: BufRead> Iterator for Groups<B> {
+    type Item = io::Result<Group>;
+
+    fn next(&mut self) -> Option<io::Result<Group>> {
+        let mut current_group = Group::new();
+        for line in &mut self.lines {
+            let line = match line {
+                Ok(l) => l,
+                Err(e) => return Some(Err(e)),
+            };
             if line.is_empty() {
-                // group is finished
-                rv.push(current_group.clone());
-                current_group.clear();
+                if current_group.size > 0 {
+                    // group is finished
+                    return Some(Ok(current_group));
+                }
             } else {
                 current_group.size += 1;
                 for c in line.chars() {
@@ -32,45 +43,113 @@ impl Group {
                 }
             }
         }
-        rv.push(current_group);
-        Ok(rv)
+        if current_group.size > 0 {
+            Some(Ok(current_group))
+        } else {
+            None
+        }
+    }
+}
+
+impl Group {
+    fn new() -> Group {
+        Group {
+            size: 0,
+            answers: HashMap::new(),
+        }
+    }
+
+    fn read(path: &str) -> io::Result<Groups<BufReader<File>>> {
+        let file = File::open(path)?;
+        Ok(Self::from_reader(BufReader::new(file)))
+    }
+
+    fn from_reader<B: BufRead>(br: B) -> Groups<B> {
+        Groups { lines: br.lines() }
     }
 
     fn insert(&mut self, question: &char) -> usize {
         let v = self.answers.get(question).unwrap_or(&0) + 1;
-        self.answers.insert(question.clone(), v);
+        self.answers.insert(*question, v);
         v
     }
 
-    fn clear(&mut self) {
-        self.size = 0;
-        self.answers.clear();
+    // Number of questions for which enough members answered yes.
+    fn count(&self, quorum: Quorum) -> usize {
+        self.answers.values()
+            .filter(|&&v| match quorum {
+                Quorum::AtLeast(k) => v >= k,
+                Quorum::Exactly(k) => v == k,
+                Quorum::Everyone => v == self.size,
+            })
+            .count()
     }
 
     fn sum_anyone(&self) -> usize {
-        self.answers.len()
+        self.count(Quorum::AtLeast(1))
     }
 
     fn sum_everyone(&self) -> usize {
-        self.answers.iter().filter(|&(_k, v)| *v == self.size).count()
+        self.count(Quorum::Everyone)
     }
 }
 
-fn groups_sums_anyone(data: &[Group]) -> usize {
-    data.iter().map(|h| h.sum_anyone()).sum()
+// Totals over every group, built one group at a time.
+#[derive(Debug, Default)]
+struct Summary {
+    groups: usize,
+    anyone: usize,
+    everyone: usize,
+    in_quorum: usize,
+    histogram: BTreeMap<char, usize>,  // question => nb people who answered yes
 }
 
-fn groups_sums_everyone(data: &[Group]) -> usize {
-    data.iter().map(|h| h.sum_everyone()).sum()
+impl Summary {
+    fn add(&mut self, group: &Group, quorum: Option<Quorum>) {
+        self.groups += 1;
+        self.anyone += group.sum_anyone();
+        self.everyone += group.sum_everyone();
+        if let Some(quorum) = quorum {
+            self.in_quorum += group.count(quorum);
+        }
+        for (question, count) in &group.answers {
+            *self.histogram.entry(*question).or_insert(0) += count;
+        }
+    }
+
+    fn from_groups<I: Iterator<Item = io::Result<Group>>>(groups: I, quorum: Option<Quorum>) -> io::Result<Summary> {
+        let mut rv = Summary::default();
+        for group in groups {
+            rv.add(&group?, quorum);
+        }
+        Ok(rv)
+    }
 }
 
 fn main() {
     let path = env::args().nth(1).expect("please supply a path");
-    let contents = Group::read(&path).expect("no content");
+    let quorum = env::args().nth(2).map(|q| {
+        if let Some(k) = q.strip_prefix("--exactly=") {
+            Quorum::Exactly(k.parse().expect("need a number of members"))
+        } else if let Some(k) = q.strip_prefix("--at-least=") {
+            Quorum::AtLeast(k.parse().expect("need a number of members"))
+        } else {
+            panic!("unknown quorum {}", q);
+        }
+    });
 
-    println!("Found {} groups", contents.len());
-    println!("Q1: anyone answered yes: {}", groups_sums_anyone(&contents));
-    println!("Q1: everyone answered yes: {}", groups_sums_everyone(&contents));
+    let groups = Group::read(&path).expect("no content");
+    let summary = Summary::from_groups(groups, quorum).expect("cannot read groups");
+
+    println!("Found {} groups", summary.groups);
+    println!("Q1: anyone answered yes: {}", summary.anyone);
+    println!("Q2: everyone answered yes: {}", summary.everyone);
+    if let Some(quorum) = quorum {
+        println!("{:?} answered yes: {}", quorum, summary.in_quorum);
+    }
+    for (question, count) in summary.histogram {
+        println!("  {}: {}", question, count);
+    }
 }
 
 #[cfg(test)]
@@ -78,16 +157,20 @@ mod tests {
     use super::*;
     use rstest::rstest;
 
+    fn summary(path: &str, quorum: Option<Quorum>) -> io::Result<Summary> {
+        Summary::from_groups(Group::read(path)?, quorum)
+    }
+
     #[rstest(path, exp_size, exp_sum,
     case(&"day_06/test_1.txt", 5, 11),
     case(&"day_06/input.txt", 490, 6735),
     )]
     fn test_anyone_sum(path: &str, exp_size: usize, exp_sum: usize) {
-        let rv = Group::read(path);
+        let rv = summary(path, None);
         assert!(rv.is_ok());
         let rv = rv.unwrap();
-        assert_eq!(rv.len(), exp_size);
-        assert_eq!(groups_sums_anyone(&rv), exp_sum);
+        assert_eq!(rv.groups, exp_size);
+        assert_eq!(rv.anyone, exp_sum);
     }
 
     #[rstest(path, exp_size, exp_sum,
@@ -95,10 +178,41 @@ mod tests {
     case(&"day_06/input.txt", 490, 3221),
     )]
     fn test_everyone_sum(path: &str, exp_size: usize, exp_sum: usize) {
-        let rv = Group::read(path);
+        let rv = summary(path, None);
         assert!(rv.is_ok());
         let rv = rv.unwrap();
-        assert_eq!(rv.len(), exp_size);
-        assert_eq!(groups_sums_everyone(&rv), exp_sum);
+        assert_eq!(rv.groups, exp_size);
+        assert_eq!(rv.everyone, exp_sum);
+    }
+
+    #[rstest(input, quorum, exp,
+    case("ab\nac\nad\n", Quorum::AtLeast(1), 4),
+    case("ab\nac\nad\n", Quorum::AtLeast(2), 1),
+    case("ab\nac\nad\n", Quorum::AtLeast(4), 0),
+    case("ab\nac\nad\n", Quorum::Exactly(1), 3),
+    case("ab\nac\nad\n", Quorum::Exactly(3), 1),
+    case("ab\nac\nad\n", Quorum::Everyone, 1),
+    case("ab\nac\n\nb\n", Quorum::Exactly(1), 3),
+    )]
+    fn test_quorum(input: &str, quorum: Quorum, exp: usize) {
+        let rv = Summary::from_groups(Group::from_reader(input.as_bytes()), Some(quorum)).unwrap();
+        assert_eq!(rv.in_quorum, exp);
+    }
+
+    #[rstest(input, exp_groups,
+    case("", 0),
+    case("\n\n", 0),
+    case("a\n\n\nb\n\n", 2),
+    case("a\nb", 1),
+    )]
+    fn test_lazy_groups(input: &str, exp_groups: usize) {
+        assert_eq!(Group::from_reader(input.as_bytes()).count(), exp_groups);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let rv = summary("day_06/test_1.txt", None).unwrap();
+        let rv: Vec<(char, usize)> = rv.histogram.into_iter().collect();
+        assert_eq!(rv, vec![('a', 8), ('b', 4), ('c', 3)]);
     }
 }
@@ -0,0 +1,359 @@
+use std::fmt;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use super::BagRule;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    UnknownColour(String),
+    Cycle(Vec<String>),  // first and last colours are the same
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GraphError::UnknownColour(c) => write!(f, "no rule for {:?} bags", c),
+            GraphError::Cycle(path) => write!(f, "bags contain themselves: {}", path.join(" -> ")),
+        }
+    }
+}
+
+// Which part of the graph to export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    All,
+    Ancestors,  // the colour and every bag which can hold it
+    Descendants,  // the colour and every bag it has to hold
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    InProgress,
+    Done,
+}
+
+// Rules indexed both ways: what a colour contains, and what it can be put in.
+#[derive(Debug, Default)]
+pub struct BagGraph {
+    contains: BTreeMap<String, Vec<(String, usize)>>,
+    contained_in: BTreeMap<String, Vec<String>>,
+}
+
+impl BagGraph {
+    pub fn new(rules: &[BagRule]) -> BagGraph {
+        let mut rv = BagGraph::default();
+        for rule in rules {
+            let mut inside: Vec<(String, usize)> = rule.contains.iter()
+                .map(|(c, n)| (c.clone(), *n))
+                .collect();
+            inside.sort();
+            for (c, _) in &inside {
+                rv.contained_in.entry(c.clone()).or_default().push(rule.colour.clone());
+            }
+            rv.contains.insert(rule.colour.clone(), inside);
+        }
+        for parents in rv.contained_in.values_mut() {
+            parents.sort();
+            parents.dedup();
+        }
+        rv
+    }
+
+    fn inside(&self, colour: &str) -> Result<&Vec<(String, usize)>, GraphError> {
+        self.contains.get(colour).ok_or_else(|| GraphError::UnknownColour(colour.to_string()))
+    }
+
+    // Every colour which can eventually hold a bag of this colour.
+    pub fn containers(&self, colour: &str) -> Result<HashSet<String>, GraphError> {
+        self.inside(colour)?;
+        let mut rv = HashSet::new();
+        let mut to_visit = vec![colour];
+        while let Some(current) = to_visit.pop() {
+            for parent in self.contained_in.get(current).into_iter().flatten() {
+                if rv.insert(parent.clone()) {
+                    to_visit.push(parent);
+                }
+            }
+        }
+        Ok(rv)
+    }
+
+    // Every colour which has to be inside a bag of this colour.
+    pub fn descendants(&self, colour: &str) -> Result<HashSet<String>, GraphError> {
+        let mut rv = HashSet::new();
+        let mut to_visit = vec![colour];
+        while let Some(current) = to_visit.pop() {
+            for (inner, _) in self.inside(current)? {
+                if rv.insert(inner.clone()) {
+                    to_visit.push(inner);
+                }
+            }
+        }
+        Ok(rv)
+    }
+
+    // Graphviz description of the rules, each edge is labelled with the amount of bags.
+    pub fn to_dot(&self, colour: &str, scope: Scope) -> Result<String, GraphError> {
+        let mut nodes: BTreeSet<&str> = match scope {
+            Scope::All => self.contains.keys().map(|c| c.as_str()).collect(),
+            Scope::Ancestors => {
+                let found = self.containers(colour)?;
+                self.contains.keys().filter(|c| found.contains(*c)).map(|c| c.as_str()).collect()
+            }
+            Scope::Descendants => {
+                let found = self.descendants(colour)?;
+                self.contains.keys().filter(|c| found.contains(*c)).map(|c| c.as_str()).collect()
+            }
+        };
+        if scope != Scope::All {
+            nodes.insert(self.contains.get_key_value(colour).unwrap().0);
+        }
+
+        let mut rv = String::from("digraph bags {\n");
+        for node in &nodes {
+            rv += &format!("    {:?};\n", node);
+        }
+        for node in &nodes {
+            for (inner, amount) in self.contains.get(*node).into_iter().flatten() {
+                if nodes.contains(inner.as_str()) {
+                    rv += &format!("    {:?} -> {:?} [label={}];\n", node, inner, amount);
+                }
+            }
+        }
+        rv += "}\n";
+        Ok(rv)
+    }
+
+    // Every way to end up with a `inner` bag inside an `outer` one, with the
+    // number of `inner` bags each way brings.
+    pub fn paths(&self, outer: &str, inner: &str) -> Result<Vec<(Vec<String>, usize)>, GraphError> {
+        self.inside(inner)?;
+        let mut rv = Vec::new();
+        let mut path = vec![outer];
+        self.collect_paths(inner, 1, &mut path, &mut rv)?;
+        Ok(rv)
+    }
+
+    fn collect_paths<'a>(
+        &'a self,
+        target: &str,
+        amount: usize,
+        path: &mut Vec<&'a str>,
+        found: &mut Vec<(Vec<String>, usize)>,
+    ) -> Result<(), GraphError> {
+        let current = *path.last().unwrap();
+        for (next, n) in self.inside(current)? {
+            if path.contains(&next.as_str()) {
+                continue;  // only simple paths, a cycle would never end
+            }
+            path.push(next);
+            if next == target {
+                found.push((path.iter().map(|c| c.to_string()).collect(), amount * n));
+            } else {
+                self.collect_paths(target, amount * n, path, found)?;
+            }
+            path.pop();
+        }
+        Ok(())
+    }
+
+    // How many bags a bag of this colour must hold.
+    pub fn count_bags(&self, colour: &str) -> Result<usize, GraphError> {
+        let mut memo = HashMap::new();
+        let mut path = Vec::new();
+        self.count_inside(colour, &mut memo, &mut path)
+    }
+
+    fn count_inside<'a>(
+        &'a self,
+        colour: &'a str,
+        memo: &mut HashMap<&'a str, Option<usize>>,  // None while it is being counted
+        path: &mut Vec<&'a str>,
+    ) -> Result<usize, GraphError> {
+        match memo.get(colour) {
+            Some(Some(v)) => return Ok(*v),
+            Some(None) => return Err(GraphError::Cycle(cycle_from(path, colour))),
+            None => {}
+        }
+
+        memo.insert(colour, None);
+        path.push(colour);
+        let mut carry = 0;
+        for (inner, amount) in self.inside(colour)? {
+            carry += amount * (1 + self.count_inside(inner, memo, path)?);
+        }
+        path.pop();
+        memo.insert(colour, Some(carry));
+        Ok(carry)
+    }
+
+    // Any set of rules where a bag ends up inside itself.
+    pub fn find_cycle(&self) -> Option<Vec<String>> {
+        let mut state = HashMap::new();
+        for colour in self.contains.keys() {
+            let mut path = Vec::new();
+            if let Some(cycle) = self.visit(colour, &mut state, &mut path) {
+                return Some(cycle);
+            }
+        }
+        None
+    }
+
+    fn visit<'a>(
+        &'a self,
+        colour: &'a str,
+        state: &mut HashMap<&'a str, Visit>,
+        path: &mut Vec<&'a str>,
+    ) -> Option<Vec<String>> {
+        match state.get(colour) {
+            Some(Visit::Done) => return None,
+            Some(Visit::InProgress) => return Some(cycle_from(path, colour)),
+            None => {}
+        }
+
+        state.insert(colour, Visit::InProgress);
+        path.push(colour);
+        for (inner, _) in self.contains.get(colour).into_iter().flatten() {
+            if let Some(cycle) = self.visit(inner, state, path) {
+                return Some(cycle);
+            }
+        }
+        path.pop();
+        state.insert(colour, Visit::Done);
+        None
+    }
+}
+
+// The end of the path starting at colour, closed by colour again.
+fn cycle_from(path: &[&str], colour: &str) -> Vec<String> {
+    let start = path.iter().position(|&c| c == colour).unwrap_or(0);
+    path[start..].iter()
+        .chain(std::iter::once(&colour))
+        .map(|c| c.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn graph(lines: &[&str]) -> BagGraph {
+        let rules: Vec<BagRule> = lines.iter().map(|l| BagRule::from_string(l).unwrap()).collect();
+        BagGraph::new(&rules)
+    }
+
+    static CYCLIC: &[&str] = &[
+        "shiny gold bags contain 2 dark red bags.",
+        "dark red bags contain 1 dark blue bag, 3 dark green bags.",
+        "dark blue bags contain no other bags.",
+        "dark green bags contain 1 shiny gold bag.",
+    ];
+
+    #[test]
+    fn test_cycle() {
+        let g = graph(CYCLIC);
+        let exp: Vec<String> = ["dark green", "shiny gold", "dark red", "dark green"].iter().map(|c| c.to_string()).collect();
+        assert_eq!(g.find_cycle(), Some(exp));
+        assert_eq!(
+            g.count_bags("shiny gold"),
+            Err(GraphError::Cycle(["shiny gold", "dark red", "dark green", "shiny gold"].iter().map(|c| c.to_string()).collect())),
+        );
+        // still fine when the cycle cannot be reached
+        assert_eq!(g.count_bags("dark blue"), Ok(0));
+        assert_eq!(g.containers("dark blue").unwrap().len(), 3);
+    }
+
+    #[rstest(colour,
+    case("pale pink"),
+    case("dark teal"),
+    )]
+    fn test_unknown_colour(colour: &str) {
+        // dark teal is only mentioned inside another rule
+        let g = graph(&["shiny gold bags contain 2 dark teal bags."]);
+        assert_eq!(g.count_bags(colour), Err(GraphError::UnknownColour(colour.to_string())));
+        if colour == "pale pink" {
+            assert!(g.containers(colour).is_err());
+        }
+    }
+
+    static EXAMPLE: &[&str] = &[
+        "light red bags contain 1 bright white bag, 2 muted yellow bags.",
+        "dark orange bags contain 3 bright white bags, 4 muted yellow bags.",
+        "bright white bags contain 1 shiny gold bag.",
+        "muted yellow bags contain 2 shiny gold bags, 9 faded blue bags.",
+        "shiny gold bags contain 1 dark olive bag, 2 vibrant plum bags.",
+        "dark olive bags contain 3 faded blue bags, 4 dotted black bags.",
+        "vibrant plum bags contain 5 faded blue bags, 6 dotted black bags.",
+        "faded blue bags contain no other bags.",
+        "dotted black bags contain no other bags.",
+    ];
+
+    #[test]
+    fn test_dot_descendants() {
+        let dot = graph(EXAMPLE).to_dot("dark olive", Scope::Descendants).unwrap();
+        assert_eq!(dot, "digraph bags {
+    \"dark olive\";
+    \"dotted black\";
+    \"faded blue\";
+    \"dark olive\" -> \"dotted black\" [label=4];
+    \"dark olive\" -> \"faded blue\" [label=3];
+}
+");
+    }
+
+    #[test]
+    fn test_dot_ancestors() {
+        let dot = graph(EXAMPLE).to_dot("bright white", Scope::Ancestors).unwrap();
+        assert_eq!(dot, "digraph bags {
+    \"bright white\";
+    \"dark orange\";
+    \"light red\";
+    \"dark orange\" -> \"bright white\" [label=3];
+    \"light red\" -> \"bright white\" [label=1];
+}
+");
+    }
+
+    #[test]
+    fn test_dot_all() {
+        let dot = graph(EXAMPLE).to_dot("shiny gold", Scope::All).unwrap();
+        assert_eq!(dot.lines().filter(|l| l.contains("->")).count(), 13);
+        assert_eq!(dot.lines().filter(|l| l.ends_with("\";")).count(), 9);
+        assert!(graph(EXAMPLE).to_dot("pale pink", Scope::Ancestors).is_err());
+    }
+
+    #[test]
+    fn test_paths() {
+        let g = graph(EXAMPLE);
+        let paths = g.paths("light red", "shiny gold").unwrap();
+        let paths: Vec<(String, usize)> = paths.into_iter().map(|(p, n)| (p.join(" > "), n)).collect();
+        assert_eq!(paths, vec![
+            ("light red > bright white > shiny gold".to_string(), 1),
+            ("light red > muted yellow > shiny gold".to_string(), 4),
+        ]);
+
+        let paths = g.paths("light red", "faded blue").unwrap();
+        let amounts: Vec<usize> = paths.iter().map(|(_, n)| *n).collect();
+        assert_eq!(amounts, vec![3, 10, 18, 12, 40]);
+        assert_eq!(g.paths("shiny gold", "light red").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_paths_with_cycle() {
+        let paths = graph(CYCLIC).paths("shiny gold", "dark blue").unwrap();
+        assert_eq!(paths, vec![(vec!["shiny gold".to_string(), "dark red".to_string(), "dark blue".to_string()], 2)]);
+    }
+
+    #[test]
+    fn test_no_cycle() {
+        let g = graph(&[
+            "a b bags contain 2 c d bags, 1 e f bag.",
+            "c d bags contain 3 e f bags.",
+            "e f bags contain no other bags.",
+        ]);
+        assert_eq!(g.find_cycle(), None);
+        assert_eq!(g.count_bags("a b"), Ok(2 * (1 + 3) + 1));
+        assert_eq!(g.count_bags("c d"), Ok(3));
+    }
+}
@@ -1,7 +1,12 @@
-use std::{io, env};
+use std::{io, env, fmt};
 use std::fs::File;
 use std::io::{BufReader, BufRead};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use regex::Regex;
+
+mod graph;
+
+use graph::{BagGraph, Scope};
 
 #[derive(Debug)]
 struct BagRule {
@@ -9,93 +14,145 @@ struct BagRule {
     contains: HashMap<String, usize>,
 }
 
-impl BagRule {
-    fn read(path: &str) -> io::Result<Vec<BagRule>> {
-        let file = File::open(path)?;
-        let br = BufReader::new(file);
-        let mut rv = Vec::new();
+// Grammar of a rule, e.g. "light red bags contain 1 bright white bag, 2 muted yellow bags."
+struct BagRuleParser {
+    rule: Regex,
+    content: Regex,
+}
 
-        for line in br.lines() {
-            let line = line?;
-            rv.push(BagRule::from_string(line));
+impl BagRuleParser {
+    fn new() -> BagRuleParser {
+        BagRuleParser {
+            rule: Regex::new(r"^(?P<colour>[a-z]+(?: [a-z]+)*?) bags? contain (?P<contents>.+?)\.?$").unwrap(),
+            content: Regex::new(r"^(?P<amount>[0-9]+) (?P<colour>[a-z]+(?: [a-z]+)*?) bags?$").unwrap(),
         }
-        Ok(rv)
     }
 
-    fn from_string(value: String) -> BagRule {
-        let rules: Vec<&str> = value.split(" contain ").collect();
+    fn parse(&self, value: &str) -> Result<BagRule, String> {
+        let caps = self.rule.captures(value.trim())
+            .ok_or_else(|| format!("expected \"<colour> bags contain ...\" in {:?}", value))?;
         let mut contains = HashMap::new();
 
-        for entry in rules[1].split(", ") {
-            let rule_parts: Vec<&str> = entry.split_whitespace().collect();
-            // I think it's time to learn regex...
-            let amount = rule_parts[0]
-                .parse().unwrap_or(0);
-            if amount == 0 {
-                break;  // "no other bags"
-            }
-            let mut contains_colour = String::new();
-            for w in rule_parts.get(1..).unwrap() {
-                if w.starts_with("bag") {
-                    break;
+        let contents = &caps["contents"];
+        if contents != "no other bags" {
+            for entry in contents.split(", ") {
+                let content = self.content.captures(entry)
+                    .ok_or_else(|| format!("expected \"<amount> <colour> bags\" in {:?}", entry))?;
+                let amount: usize = content["amount"].parse()
+                    .map_err(|_| format!("invalid amount in {:?}", entry))?;
+                if amount == 0 {
+                    return Err(format!("cannot contain 0 bags in {:?}", entry));
+                }
+                if contains.insert(content["colour"].to_string(), amount).is_some() {
+                    return Err(format!("{} bags are listed twice", &content["colour"]));
                 }
-                contains_colour += " ";
-                contains_colour += w;
             }
-            contains.insert(contains_colour.trim().to_string(), amount);
         }
 
-        BagRule {
-            colour: rules[0].replace(" bags", ""),
+        Ok(BagRule {
+            colour: caps["colour"].to_string(),
             contains,
-        }
+        })
     }
+}
 
-    fn find_rules(rules: &Vec<BagRule>, colour: &str) -> HashSet<String> {
-        let mut can_contain = HashSet::new();
-        for rule in rules.iter().filter(|&r| r.contains.contains_key(colour)) {
-            can_contain.insert(rule.colour.clone());
-        }
+impl BagRule {
+    fn read(path: &str) -> io::Result<Vec<BagRule>> {
+        let file = File::open(path)?;
+        let br = BufReader::new(file);
+        let parser = BagRuleParser::new();
+        let mut rv = Vec::new();
 
-        // clone can_contain to allow modification of the original
-        // this is recursive... We should stop when we find rules that cannot contain bags.
-        for colour in can_contain.clone() {
-            for more in Self::find_rules(rules, &colour) {
-                can_contain.insert(more);
+        for (idx, line) in br.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
             }
+            let rule = parser.parse(&line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", idx + 1, e)))?;
+            rv.push(rule);
         }
-
-        can_contain
+        Ok(rv)
     }
 
-    fn count_bags(rules: &Vec<BagRule>, colour: &str) -> usize {
-        let my_rule = rules.iter()
-            .filter(|&r| r.colour == colour)
-            .next().unwrap();  // should be only 1
-
-        let mut carry = my_rule.contains.values().sum();
-        println!("{} can carry {} from {:?}", colour, carry, my_rule.contains.keys());
+    #[cfg(test)]
+    fn from_string(value: &str) -> Result<BagRule, String> {
+        BagRuleParser::new().parse(value)
+    }
+}
 
-        // Then for every bad we can carry inside this one check the ones inside.
-        // This is recursive and will stop when we reach a rule that has no bags inside.
-        for rule in &my_rule.contains {
-            carry += Self::count_bags(rules, &rule.0) * rule.1;
+// Canonical text of the rule, contents sorted by colour.
+impl fmt::Display for BagRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} bags contain ", self.colour)?;
+        if self.contains.is_empty() {
+            return write!(f, "no other bags.");
         }
-
-        carry
+        let mut contents: Vec<(&String, &usize)> = self.contains.iter().collect();
+        contents.sort();
+        let contents: Vec<String> = contents.iter()
+            .map(|(colour, &amount)| format!("{} {} {}", amount, colour, if amount == 1 { "bag" } else { "bags" }))
+            .collect();
+        write!(f, "{}.", contents.join(", "))
     }
 }
 
 fn main() {
     let path = env::args().nth(1).expect("please supply a path");
     let contents = BagRule::read(&path).expect("no content");
+    let args: Vec<String> = env::args().skip(2).collect();
+    let option = |name: &str| args.iter()
+        .find_map(|a| a.strip_prefix(&format!("--{}=", name)).map(|v| v.to_string()));
+    let colour = args.iter()
+        .find(|a| !a.starts_with("--"))
+        .cloned()
+        .unwrap_or_else(|| "shiny gold".to_string());
+    let graph = BagGraph::new(&contents);
+
+    if args.iter().any(|a| a == "--canonical") {
+        for rule in &contents {
+            println!("{}", rule);
+        }
+        return;
+    }
+    if let Some(scope) = option("dot") {
+        let scope = match scope.as_str() {
+            "all" => Scope::All,
+            "ancestors" => Scope::Ancestors,
+            "descendants" => Scope::Descendants,
+            _ => panic!("unknown scope {}, expected all, ancestors or descendants", scope),
+        };
+        match graph.to_dot(&colour, scope) {
+            Ok(dot) => print!("{}", dot),
+            Err(e) => eprintln!("{}", e),
+        }
+        return;
+    }
+    if let Some(outer) = option("paths-from") {
+        match graph.paths(&outer, &colour) {
+            Ok(paths) => {
+                for (path, amount) in paths {
+                    println!("{} => {} {} bags", path.join(" > "), amount, colour);
+                }
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+        return;
+    }
 
     println!("Found {} rules", contents.len());
-    let carry_on = BagRule::find_rules(&contents, &"shiny gold");
-    println!("Q1: Found {} possibly carry on", carry_on.len());
+    if let Some(cycle) = graph.find_cycle() {
+        eprintln!("Bags contain themselves: {}", cycle.join(" -> "));
+    }
 
-    let must_carry = BagRule::count_bags(&contents, &"shiny gold");
-    println!("Q2: We must carry {} bags inside a shiny gold bag", must_carry);
+    match graph.containers(&colour) {
+        Ok(carry_on) => println!("Q1: Found {} possibly carry on", carry_on.len()),
+        Err(e) => println!("Q1: {}", e),
+    }
+    match graph.count_bags(&colour) {
+        Ok(must_carry) => println!("Q2: We must carry {} bags inside a {} bag", must_carry, colour),
+        Err(e) => println!("Q2: {}", e),
+    }
 }
 
 #[cfg(test)]
@@ -133,19 +190,77 @@ mod tests {
     ),
     )]
     fn test_from_string(input: &str, colour: &str, contains: HashMap<String, usize>) {
-        let rule = BagRule::from_string(input.to_string());
+        let rule = BagRule::from_string(input).unwrap();
         println!("Loaded {:?}", rule);
         assert_eq!(rule.colour, colour);
         assert_eq!(rule.contains, contains);
     }
 
+    #[rstest(input, colour, contains,
+    case("bright white bags contain 1 shiny gold bag", "bright white", map!{"shiny gold" => 1}),
+    case("bright white bag contain 2 shiny gold bag.", "bright white", map!{"shiny gold" => 2}),
+    case("very bright white bags contain 12 pale shiny gold bags.", "very bright white", map!{"pale shiny gold" => 12}),
+    case("  bright white bags contain no other bags.  ", "bright white", HashMap::new()),
+    )]
+    fn test_from_string_variants(input: &str, colour: &str, contains: HashMap<String, usize>) {
+        let rule = BagRule::from_string(input).unwrap();
+        assert_eq!(rule.colour, colour);
+        assert_eq!(rule.contains, contains);
+    }
+
+    #[rstest(input,
+    case(""),
+    case("light red bags contain"),
+    case("light red bags hold 1 bright white bag."),
+    case("light red bags contain one bright white bag."),
+    case("light red bags contain 0 bright white bags."),
+    case("light red bags contain 1 bright white."),
+    case("light red bags contain 1 bright white bag,2 muted yellow bags."),
+    case("light red bags contain 1 bright white bag, 2 bright white bags."),
+    case("Light red bags contain no other bags."),
+    )]
+    fn test_malformed_rule(input: &str) {
+        assert!(BagRule::from_string(input).is_err());
+    }
+
+    #[rstest(input, exp,
+    case("light red bags contain 2 muted yellow bags, 1 bright white bag.", "light red bags contain 1 bright white bag, 2 muted yellow bags."),
+    case("faded blue bags contain no other bags", "faded blue bags contain no other bags."),
+    case("bright white bag contain 1 shiny gold bags", "bright white bags contain 1 shiny gold bag."),
+    )]
+    fn test_pretty_print(input: &str, exp: &str) {
+        assert_eq!(BagRule::from_string(input).unwrap().to_string(), exp);
+    }
+
+    #[test]
+    fn test_read_reports_line() {
+        let path = std::env::temp_dir().join("day_07_malformed.txt");
+        std::fs::write(&path, "faded blue bags contain no other bags.\nlight red bags contain many bags.\n").unwrap();
+        let err = BagRule::read(path.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().starts_with("line 2: "), "{}", err);
+    }
+
+    #[rstest(path,
+    case("day_07/test_1.txt"),
+    case("day_07/input.txt"),
+    )]
+    fn test_canonical_input(path: &str) {
+        let content = std::fs::read_to_string(path).unwrap();
+        for line in content.lines() {
+            let rule = BagRule::from_string(line).unwrap();
+            let again = BagRule::from_string(&rule.to_string()).unwrap();
+            assert_eq!(rule.colour, again.colour);
+            assert_eq!(rule.contains, again.contains);
+        }
+    }
+
     #[rstest(path, colour, containers,
     case(&"day_07/test_1.txt", &"shiny gold", 4),
     )]
     fn test_can_contain(path: &str, colour: &str, containers: usize) {
         let rules = BagRule::read(path).unwrap();
         println!("Loaded {} rules", rules.len());
-        let found = BagRule::find_rules(&rules, colour);
+        let found = BagGraph::new(&rules).containers(colour).unwrap();
         println!("Found {:?}", found);
         assert_eq!(found.len(), containers);
     }
@@ -157,7 +272,7 @@ mod tests {
     fn test_must_carry(path: &str, colour: &str, must_carry: usize) {
         let rules = BagRule::read(path).unwrap();
         println!("Loaded {} rules", rules.len());
-        let carry = BagRule::count_bags(&rules, colour);
+        let carry = BagGraph::new(&rules).count_bags(colour).unwrap();
         assert_eq!(carry, must_carry);
     }
 }
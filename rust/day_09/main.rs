@@ -1,6 +1,7 @@
 use std::{io, env};
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
-use std::io::{BufReader, BufRead};
+use std::io::{BufReader, BufRead, Read};
 
 fn read(path: &str) -> io::Result<Vec<u64>> {
     let file = File::open(path)?;
@@ -12,94 +13,189 @@ fn read(path: &str) -> io::Result<Vec<u64>> {
         if let Ok(v) = line.parse() {
             rv.push(v);
         } else {
-            return Err(io::Error::new(io::ErrorKind::Other, "invalid line"));
+            return Err(io::Error::other("invalid line"));
         }
     }
     Ok(rv)
 }
 
-fn is_valid(history: &[u64], value: u64) -> bool {
-    // brute force check, we may want something a bit more clever if we can.
-    for (idx, a) in history.iter().enumerate() {
-        for (_j, b) in history.iter().enumerate().filter(|&(i, _)| i > idx) {
-            if a + b == value {
-                return true;
-            }
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Invalid {
+    position: usize,  // 0 based index in the stream
+    value: u64,
+}
+
+// Sliding window over the last `preamble` values which keeps a count of every
+// pair sum, so checking a value is a lookup instead of a rescan of the window.
+#[derive(Debug)]
+struct Validator {
+    preamble: usize,
+    window: VecDeque<u64>,
+    pair_sums: HashMap<u128, usize>,  // u128 so two large values cannot overflow
+    position: usize,
+}
+
+impl Validator {
+    fn new(preamble: usize) -> Validator {
+        Validator {
+            preamble,
+            window: VecDeque::with_capacity(preamble),
+            pair_sums: HashMap::new(),
+            position: 0,
         }
     }
-    false
-}
 
-fn first_invalid(values: &[u64], look_back: usize) -> Option<u64> {
-    let mut history: Vec<u64> = Vec::new();
+    fn is_valid(&self, value: u64) -> bool {
+        self.pair_sums.get(&(value as u128)).is_some_and(|&c| c > 0)
+    }
 
-    for v in values {
-        if history.len() < look_back {
-            history.push(*v);
+    // Consume the next value, returning it if it is not the sum of a pair in the window.
+    fn push(&mut self, value: u64) -> Option<Invalid> {
+        let rv = if self.window.len() < self.preamble || self.is_valid(value) {
+            None
         } else {
-            if !is_valid(&history, *v) {
-                return Some(v.clone());
+            Some(Invalid { position: self.position, value })
+        };
+
+        if self.preamble > 0 && self.window.len() == self.preamble {
+            let oldest = self.window.pop_front().unwrap();
+            for other in &self.window {
+                let sum = oldest as u128 + *other as u128;
+                let count = self.pair_sums.get_mut(&sum).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    self.pair_sums.remove(&sum);
+                }
+            }
+        }
+        if self.preamble > 0 {
+            for other in &self.window {
+                *self.pair_sums.entry(value as u128 + *other as u128).or_insert(0) += 1;
             }
-            history.remove(0);
-            history.push(*v);
+            self.window.push_back(value);
         }
+        self.position += 1;
+        rv
     }
-    None
-}
 
-fn has_weakness(values: &[u64], invalid: u64, size: usize) -> Option<Vec<u64>> {
-    let mut rv = Vec::new();
+    // Validate a stream of one value per line, reporting every invalid value.
+    fn validate<R: Read>(reader: R, preamble: usize) -> io::Result<Vec<Invalid>> {
+        let mut validator = Validator::new(preamble);
+        let mut rv = Vec::new();
 
-    for v in values {
-        if rv.len() < size {
-            rv.push(*v);
-        } else {
-            let sum: u64 = rv.iter().sum();
-            if sum == invalid {
-                return Some(rv);
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            let value = line.parse()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("invalid line {:?}", line)))?;
+            if let Some(invalid) = validator.push(value) {
+                rv.push(invalid);
             }
-            rv.remove(0);
-            rv.push(*v);
         }
+        Ok(rv)
+    }
+}
+
+// A contiguous range of at least two values, `start..=end`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Weakness {
+    start: usize,
+    end: usize,
+    min: u64,
+    max: u64,
+}
+
+impl Weakness {
+    fn new(values: &[u64], start: usize, end: usize) -> Weakness {
+        let range = &values[start..=end];
+        Weakness {
+            start,
+            end,
+            min: *range.iter().min().unwrap(),
+            max: *range.iter().max().unwrap(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.end - self.start + 1
+    }
+
+    fn encryption_weakness(&self) -> u64 {
+        self.min + self.max
     }
-    None
 }
 
-fn search_weakness(values: &[u64], invalid: u64) -> Option<Vec<u64>> {
-    eprintln!("Scanning...");
-    for size in 2..values.len() {
-        if size % 10 == 0 {
-            eprintln!("Scanning for size {}", size);
+// Two pointers over the values: grow the range on the right and shrink it on
+// the left while it is too large. Values are never negative so this is O(n).
+fn search_weakness(values: &[u64], invalid: u64) -> Option<Weakness> {
+    let mut start = 0;
+    let mut sum: u128 = 0;
+
+    for (end, v) in values.iter().enumerate() {
+        sum += *v as u128;
+        while sum > invalid as u128 && start < end {
+            sum -= values[start] as u128;
+            start += 1;
         }
-        let rv = has_weakness(values, invalid, size);
-        if rv.is_some() {
-            return rv;
+        if sum == invalid as u128 && end > start {
+            return Some(Weakness::new(values, start, end));
         }
     }
     None
 }
 
+// Every range summing to the invalid value, found by looking up in the prefix
+// sums seen so far the one that is exactly `invalid` behind the current one.
+fn search_all_weaknesses(values: &[u64], invalid: u64) -> Vec<Weakness> {
+    let mut prefixes: HashMap<u128, Vec<usize>> = HashMap::new();
+    let mut prefix: u128 = 0;
+    let mut rv = Vec::new();
+
+    prefixes.entry(0).or_default().push(0);
+    for (end, v) in values.iter().enumerate() {
+        prefix += *v as u128;
+        if let Some(starts) = prefix.checked_sub(invalid as u128).and_then(|p| prefixes.get(&p)) {
+            for &start in starts.iter().filter(|&&start| start < end) {
+                rv.push(Weakness::new(values, start, end));
+            }
+        }
+        prefixes.entry(prefix).or_default().push(end + 1);
+    }
+    rv
+}
+
 fn main() {
     let path = env::args().nth(1).expect("please supply a path");
-    let contents = read(&path).expect("no content");
+    let preamble = env::args()
+        .nth(2)
+        .map_or(25, |p| p.parse().expect("need a preamble length"));
+
+    let file = File::open(&path).expect("cannot open file");
+    let invalids = Validator::validate(file, preamble).expect("no content");
+    for invalid in &invalids {
+        eprintln!("invalid value {} at position {}", invalid.value, invalid.position);
+    }
 
-    let invalid = first_invalid(&contents, 25);
-    if let Some(invalid) = invalid {
+    if let Some(invalid) = invalids.first() {
+        let invalid = invalid.value;
+        let contents = read(&path).expect("no content");
         println!("Q1: first invalid entry: {}", invalid);
 
         let weakness = search_weakness(&contents, invalid);
         if let Some(weakness) = weakness {
-            let min = weakness.iter().min().unwrap();
-            let max = weakness.iter().max().unwrap();
-            println!("Q2: list of {} elem, smallest {} and largest {}: {}",
+            println!("Q2: list of {} elem from {} to {}, smallest {} and largest {}: {}",
                 weakness.len(),
-                min,
-                max,
-                min + max
+                weakness.start,
+                weakness.end,
+                weakness.min,
+                weakness.max,
+                weakness.encryption_weakness()
             );
         } else {
             println!("No weakness found");
         }
+        for weakness in search_all_weaknesses(&contents, invalid) {
+            eprintln!("range {}..={} => {}", weakness.start, weakness.end, weakness.encryption_weakness());
+        }
     } else {
         println!("Valid XMAS");
     }
@@ -114,7 +210,42 @@ mod tests {
     case(&[35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309, 576,], Some(127))
     )]
     fn test_lookback_of_5(input: &[u64], exp: Option<u64>) {
-        assert_eq!(first_invalid(input, 5), exp);
+        let mut validator = Validator::new(5);
+        let rv = input.iter().find_map(|&v| validator.push(v));
+        assert_eq!(rv.map(|invalid| invalid.value), exp);
+    }
+
+    #[rstest(input, preamble, exp,
+    case(&[35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309, 576,], 5, vec![Invalid { position: 14, value: 127 }]),
+    case(&[1, 2, 3, 100, 5, 105, 200], 2, vec![Invalid { position: 3, value: 100 }, Invalid { position: 4, value: 5 }, Invalid { position: 6, value: 200 }]),
+    case(&[1, 2, 3, 5, 8, 13], 2, vec![]),
+    case(&[5, 5, 10, 10], 2, vec![Invalid { position: 3, value: 10 }]),
+    case(&[5, 6, 7], 0, vec![Invalid { position: 0, value: 5 }, Invalid { position: 1, value: 6 }, Invalid { position: 2, value: 7 }]),
+    )]
+    fn test_validate_stream(input: &[u64], preamble: usize, exp: Vec<Invalid>) {
+        let text: String = input.iter().map(|v| format!("{}\n", v)).collect();
+        let rv = Validator::validate(text.as_bytes(), preamble).expect("valid stream");
+        assert_eq!(rv, exp);
+    }
+
+    #[test]
+    fn test_push_large_values() {
+        let mut validator = Validator::new(2);
+        assert_eq!(validator.push(u64::MAX), None);
+        assert_eq!(validator.push(1), None);
+        // u64::MAX + 1 does not fit in a u64, it cannot match anything
+        assert_eq!(validator.push(0), Some(Invalid { position: 2, value: 0 }));
+        assert_eq!(validator.push(1), None);
+
+        let mut validator = Validator::new(2);
+        validator.push(u64::MAX - 1);
+        validator.push(1);
+        assert_eq!(validator.push(u64::MAX), None);
+    }
+
+    #[test]
+    fn test_validate_rejects_garbage() {
+        assert!(Validator::validate("1\n2\nthree\n".as_bytes(), 2).is_err());
     }
 
     #[rstest(input, weakness, exp_min, exp_max,
@@ -124,7 +255,37 @@ mod tests {
         let rv = search_weakness(input, weakness);
         assert!(rv.is_some());
         let rv = rv.unwrap();
-        assert_eq!(*rv.iter().min().unwrap(), exp_min);
-        assert_eq!(*rv.iter().max().unwrap(), exp_max);
+        assert_eq!(rv.min, exp_min);
+        assert_eq!(rv.max, exp_max);
+        assert_eq!((rv.start, rv.end), (2, 5));
+    }
+
+    #[rstest(input, invalid, exp,
+    case(&[35, 20, 15, 25, 47, 40, 62, 55, 65, 95, 102, 117, 150, 182, 127, 219, 299, 277, 309, 576,], 127, vec![(2, 5)]),
+    case(&[1, 2, 3, 3, 2, 1], 6, vec![(0, 2), (2, 3), (3, 5)]),
+    case(&[3, 0, 3, 0], 3, vec![(0, 1), (1, 2), (1, 3), (2, 3)]),
+    case(&[6, 1, 1], 6, vec![]),
+    case(&[], 6, vec![]),
+    )]
+    fn test_search_all_weaknesses(input: &[u64], invalid: u64, exp: Vec<(usize, usize)>) {
+        let rv: Vec<(usize, usize)> = search_all_weaknesses(input, invalid)
+            .iter()
+            .map(|w| (w.start, w.end))
+            .collect();
+        assert_eq!(rv, exp);
+        assert_eq!(search_weakness(input, invalid).map(|w| (w.start, w.end)), exp.first().cloned());
+    }
+
+    #[test]
+    fn test_search_weakness_large_input() {
+        let mut values: Vec<u64> = (0..2_000_000).map(|i| 1_000_000 + (i % 1000)).collect();
+        let start = 1_500_000;
+        let invalid: u64 = values[start..start + 3].iter().sum::<u64>() + 1;
+        values[start + 3] = 1;
+
+        let rv = search_all_weaknesses(&values, invalid);
+        assert_eq!(rv.len(), 1);
+        assert_eq!((rv[0].start, rv[0].end), (start, start + 3));
+        assert_eq!(search_weakness(&values, invalid), Some(rv[0]));
     }
 }
@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, BTreeSet};
+use num_bigint::BigUint;
+
+// Adapters sorted by joltage, the last one is the device.
+// Two adapters can be linked when their difference is one of the allowed gaps.
+#[derive(Debug, Clone)]
+pub struct ChainAnalysis {
+    adapters: Vec<usize>,
+    gaps: BTreeSet<usize>,
+}
+
+impl ChainAnalysis {
+    pub fn new(adapters: &[usize], gaps: &[usize]) -> ChainAnalysis {
+        let mut adapters = adapters.to_vec();
+        adapters.sort_unstable();
+        ChainAnalysis {
+            adapters,
+            // a gap of 0 never links anything: joltages have to go up
+            gaps: gaps.iter().cloned().filter(|&g| g > 0).collect(),
+        }
+    }
+
+    pub fn max_gap(&self) -> usize {
+        *self.gaps.iter().next_back().unwrap_or(&0)
+    }
+
+    fn joltage(&self, idx: Option<usize>) -> usize {
+        idx.map_or(0, |i| self.adapters[i])
+    }
+
+    fn links(&self, from: Option<usize>, to: usize) -> bool {
+        let from = self.joltage(from);
+        to > from && self.gaps.contains(&(to - from))
+    }
+
+    // Histogram of the jolt differences when using every adapter,
+    // or the (from, to) joltages of the first link which is not allowed.
+    pub fn histogram(&self) -> Result<BTreeMap<usize, usize>, (usize, usize)> {
+        let mut diffs = BTreeMap::new();
+
+        let mut current = 0;
+        for &adapter in &self.adapters {
+            let diff = adapter - current;
+            if !self.gaps.contains(&diff) {
+                return Err((current, adapter));
+            }
+            *diffs.entry(diff).or_insert(0) += 1;
+            current = adapter;
+        }
+        Ok(diffs)
+    }
+
+    // Number of ways to link the outlet to the device.
+    pub fn count(&self) -> BigUint {
+        let max_gap = self.max_gap();
+        let mut counts: Vec<BigUint> = Vec::with_capacity(self.adapters.len());
+
+        for (idx, &a) in self.adapters.iter().enumerate() {
+            let mut v = if self.links(None, a) {
+                BigUint::from(1u32)
+            } else {
+                BigUint::from(0u32)
+            };
+            for prev in (0..idx).rev() {
+                if a - self.adapters[prev] > max_gap {
+                    break;
+                }
+                if self.links(Some(prev), a) {
+                    v += &counts[prev];
+                }
+            }
+            counts.push(v);
+        }
+        counts.pop().unwrap_or_else(|| BigUint::from(0u32))
+    }
+
+    pub fn chains(&self) -> Chains<'_> {
+        Chains::new(self)
+    }
+}
+
+// Lazily walk every valid chain from the outlet to the device, in lexicographic order.
+pub struct Chains<'a> {
+    analysis: &'a ChainAnalysis,
+    // can this adapter still reach the device?
+    reaches_device: Vec<bool>,
+    stack: Vec<Vec<usize>>,
+}
+
+impl<'a> Chains<'a> {
+    fn new(analysis: &'a ChainAnalysis) -> Chains<'a> {
+        let adapters = &analysis.adapters;
+        let mut reaches_device = vec![false; adapters.len()];
+        for idx in (0..adapters.len()).rev() {
+            reaches_device[idx] = idx == adapters.len() - 1 || (idx + 1..adapters.len())
+                .take_while(|&next| adapters[next] - adapters[idx] <= analysis.max_gap())
+                .any(|next| reaches_device[next] && analysis.links(Some(idx), adapters[next]));
+        }
+
+        let mut rv = Chains {
+            analysis,
+            reaches_device,
+            stack: Vec::new(),
+        };
+        rv.push_next(&[]);
+        rv
+    }
+
+    fn push_next(&mut self, path: &[usize]) {
+        let last = path.last().cloned();
+        let start = last.map_or(0, |l| l + 1);
+        let adapters = &self.analysis.adapters;
+        let next: Vec<usize> = (start..adapters.len())
+            .take_while(|&n| adapters[n] <= self.analysis.joltage(last) + self.analysis.max_gap())
+            .filter(|&n| self.reaches_device[n] && self.analysis.links(last, adapters[n]))
+            .collect();
+        // reversed so the smallest joltage is popped first
+        for n in next.into_iter().rev() {
+            let mut p = path.to_vec();
+            p.push(n);
+            self.stack.push(p);
+        }
+    }
+}
+
+impl Iterator for Chains<'_> {
+    type Item = Vec<usize>;
+
+    fn next(&mut self) -> Option<Vec<usize>> {
+        while let Some(path) = self.stack.pop() {
+            if *path.last().unwrap() == self.analysis.adapters.len() - 1 {
+                return Some(path.iter().map(|&i| self.analysis.adapters[i]).collect());
+            }
+            self.push_next(&path);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    // test_1.txt with its device
+    static EXAMPLE: &[usize] = &[16, 10, 15, 5, 1, 11, 7, 19, 6, 12, 4, 22];
+
+    #[rstest(adapters, gaps, exp,
+    case(EXAMPLE, &[1, 2, 3], 8),
+    case(EXAMPLE, &[1, 3], 2),
+    case(EXAMPLE, &[3], 0),
+    case(&[1, 2, 3, 4], &[1, 2, 3], 7),
+    case(&[1, 2, 3, 4], &[1, 2], 5),
+    case(&[2, 4, 6, 8], &[2, 4], 5),
+    case(&[], &[1, 2, 3], 0),
+    )]
+    fn test_count(adapters: &[usize], gaps: &[usize], exp: u32) {
+        let analysis = ChainAnalysis::new(adapters, gaps);
+        assert_eq!(analysis.count(), BigUint::from(exp));
+        assert_eq!(analysis.chains().count(), exp as usize);
+    }
+
+    #[test]
+    fn test_count_does_not_overflow() {
+        // tribonacci like growth goes past u64 well before 200 adapters
+        let adapters: Vec<usize> = (1..=200).collect();
+        let analysis = ChainAnalysis::new(&adapters, &[1, 2, 3]);
+        let count = analysis.count();
+        assert!(count > BigUint::from(u64::MAX));
+        assert_eq!(count.to_string(), "52622583840983769603765180599790256716084480555530641");
+    }
+
+    #[test]
+    fn test_chains_are_ordered() {
+        let analysis = ChainAnalysis::new(&[1, 2, 3, 4], &[1, 2, 3]);
+        let chains: Vec<Vec<usize>> = analysis.chains().collect();
+        assert_eq!(chains, vec![
+            vec![1, 2, 3, 4],
+            vec![1, 2, 4],
+            vec![1, 3, 4],
+            vec![1, 4],
+            vec![2, 3, 4],
+            vec![2, 4],
+            vec![3, 4],
+        ]);
+    }
+
+    #[rstest(adapters, gaps, exp,
+    case(EXAMPLE, &[1, 2, 3], Ok(vec![(1, 7), (3, 5)])),
+    case(EXAMPLE, &[1, 3], Ok(vec![(1, 7), (3, 5)])),
+    case(EXAMPLE, &[1], Err((1, 4))),
+    case(&[2, 4, 8], &[2, 4], Ok(vec![(2, 2), (4, 1)])),
+    )]
+    fn test_histogram(adapters: &[usize], gaps: &[usize], exp: Result<Vec<(usize, usize)>, (usize, usize)>) {
+        let analysis = ChainAnalysis::new(adapters, gaps);
+        let rv = analysis.histogram().map(|h| h.into_iter().collect::<Vec<_>>());
+        assert_eq!(rv, exp);
+    }
+}
@@ -0,0 +1,159 @@
+use super::{Action, Direction};
+
+// Navigation with turns of any angle.
+//
+// Headings are whole degrees, counter-clockwise from east, kept in 0..360 so
+// they stay exact however many turns are made. Positions are f64; quarter turns
+// use exact unit vectors so right-angle inputs give the same result as the
+// integer mode. Only the reported distance is rounded, to the nearest integer
+// with halves going away from zero.
+
+// (east, north) unit vector for a heading in degrees.
+fn unit(degrees: i32) -> (f64, f64) {
+    match degrees.rem_euclid(360) {
+        0 => (1.0, 0.0),
+        90 => (0.0, 1.0),
+        180 => (-1.0, 0.0),
+        270 => (0.0, -1.0),
+        d => {
+            let rad = (d as f64).to_radians();
+            (rad.cos(), rad.sin())
+        }
+    }
+}
+
+// Counter-clockwise angle for a rotation action, None for any other action.
+fn turn(action: &Action) -> Option<i32> {
+    match action.action {
+        Direction::L => Some(action.value),
+        Direction::R => Some(-action.value),
+        _ => None,
+    }
+}
+
+fn translate(action: &Action) -> Option<(f64, f64)> {
+    let v = action.value as f64;
+    match action.action {
+        Direction::N => Some((0.0, v)),
+        Direction::S => Some((0.0, -v)),
+        Direction::E => Some((v, 0.0)),
+        Direction::W => Some((-v, 0.0)),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FreeShip {
+    pub heading: i32,
+    pub north: f64,
+    pub east: f64,
+}
+
+impl FreeShip {
+    fn new() -> FreeShip {
+        FreeShip { heading: 0, north: 0.0, east: 0.0 }
+    }
+
+    fn apply(&mut self, action: &Action) {
+        if let Some(angle) = turn(action) {
+            self.heading = (self.heading + angle).rem_euclid(360);
+        } else if let Some((east, north)) = translate(action) {
+            self.east += east;
+            self.north += north;
+        } else {
+            let (east, north) = unit(self.heading);
+            self.east += east * action.value as f64;
+            self.north += north * action.value as f64;
+        }
+    }
+
+    pub fn follow_q1(actions: &[Action]) -> FreeShip {
+        let mut ship = FreeShip::new();
+        for a in actions {
+            ship.apply(a);
+        }
+        ship
+    }
+
+    pub fn follow_q2(actions: &[Action]) -> FreeShip {
+        let mut ship = FreeShip::new();
+        let (mut w_east, mut w_north) = (10.0, 1.0);
+
+        for a in actions {
+            if let Some(angle) = turn(a) {
+                let (cos, sin) = unit(angle);
+                let east = w_east * cos - w_north * sin;
+                w_north = w_east * sin + w_north * cos;
+                w_east = east;
+            } else if let Some((east, north)) = translate(a) {
+                w_east += east;
+                w_north += north;
+            } else {
+                ship.east += w_east * a.value as f64;
+                ship.north += w_north * a.value as f64;
+            }
+        }
+        ship
+    }
+
+    pub fn travelled(&self) -> i64 {
+        (self.north.abs() + self.east.abs()).round() as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Ship;
+    use rstest::rstest;
+
+    fn actions(line: &str) -> Vec<Action> {
+        line.split_whitespace()
+            .map(|a| Action::from_string(a.to_string()).unwrap())
+            .collect()
+    }
+
+    #[rstest(path, case("day_12/test_1.txt"), case("day_12/input.txt"))]
+    fn test_matches_integer_mode(path: &str) {
+        let contents = Action::read(path).unwrap();
+
+        let ship = Ship::follow_q1(&contents).unwrap();
+        let free = FreeShip::follow_q1(&contents);
+        assert_eq!((free.east, free.north), (ship.east as f64, ship.north as f64));
+
+        let ship = Ship::follow_q2(&contents).unwrap();
+        let free = FreeShip::follow_q2(&contents);
+        assert_eq!((free.east, free.north), (ship.east as f64, ship.north as f64));
+    }
+
+    #[rstest(input, exp_heading, exp_east, exp_north, exp_travelled,
+    case("L45 F10", 45, 7.0710678, 7.0710678, 14),
+    case("R45 F10", 315, 7.0710678, -7.0710678, 14),
+    case("L45 F10 R90 F10", 315, 14.1421356, 0.0, 14),
+    case("R30 F2 L390", 0, 1.7320508, -1.0, 3),
+    case("L720 F3", 0, 3.0, 0.0, 3),
+    )]
+    fn test_q1_any_angle(input: &str, exp_heading: i32, exp_east: f64, exp_north: f64, exp_travelled: i64) {
+        let ship = FreeShip::follow_q1(&actions(input));
+        assert_eq!(ship.heading, exp_heading);
+        assert!((ship.east - exp_east).abs() < 1e-6, "{:?}", ship);
+        assert!((ship.north - exp_north).abs() < 1e-6, "{:?}", ship);
+        assert_eq!(ship.travelled(), exp_travelled);
+    }
+
+    #[test]
+    fn test_q2_eight_turns_of_45_is_a_full_turn() {
+        let ship = FreeShip::follow_q2(&actions("L45 L45 L45 L45 L45 L45 L45 L45 F1"));
+        assert!((ship.east - 10.0).abs() < 1e-9, "{:?}", ship);
+        assert!((ship.north - 1.0).abs() < 1e-9, "{:?}", ship);
+        assert_eq!(ship.travelled(), 11);
+    }
+
+    #[test]
+    fn test_q2_any_angle() {
+        // waypoint (10, 0) turned by 45 degrees clockwise
+        let ship = FreeShip::follow_q2(&actions("S1 R45 F1"));
+        assert!((ship.east - 7.0710678).abs() < 1e-6, "{:?}", ship);
+        assert!((ship.north + 7.0710678).abs() < 1e-6, "{:?}", ship);
+    }
+}
@@ -2,6 +2,11 @@ use std::{io, env};
 use std::fs::File;
 use std::io::{BufReader, BufRead};
 
+mod heading;
+mod trace;
+
+use trace::Step;
+
 #[derive(Clone,Copy,Debug,PartialEq,Eq)]
 enum Direction {
     N,
@@ -41,21 +46,21 @@ impl Direction {
             .filter(|&(_, a)| a == self)
             .map(|(i, _)| i)
             .collect();
-        let n = action.value / 90;
+        let n = (action.value / 90).rem_euclid(order.len() as i32) as usize;
         let index = if other == L {
             // + len to ensure it stays positive
-            (index[0] + order.len() - n as usize) % order.len()
+            (index[0] + order.len() - n) % order.len()
         } else {
-            (index[0] + n as usize) % order.len()
+            (index[0] + n) % order.len()
         };
-        if index as usize >= order.len() {
+        if index >= order.len() {
             eprintln!("Index of {:?} is {:?}?", self, index);
         }
         order[index]
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct Action {
     action: Direction,
     value: i32,
@@ -64,10 +69,25 @@ struct Action {
 impl Action {
     fn from_string(line: String) -> Option<Action> {
         let act = Direction::from_line(&line)?;
-        let v = (&line[1..]).parse().unwrap();
+        let v = line[1..].parse().unwrap();
         Some(Action{action: act, value: v})
     }
 
+    fn is_rotation(&self) -> bool {
+        self.action == Direction::L || self.action == Direction::R
+    }
+
+    // Integer navigation only knows about quarter turns.
+    fn check_right_angle(&self, index: usize) -> Result<(), String> {
+        if self.is_rotation() && self.value % 90 != 0 {
+            return Err(format!(
+                "action {}: {:?}{} is not a multiple of 90 degrees, use --continuous",
+                index + 1, self.action, self.value,
+            ));
+        }
+        Ok(())
+    }
+
     fn read(path: &str) -> io::Result<Vec<Action>> {
         let file = File::open(path)?;
         let br = BufReader::new(file);
@@ -82,7 +102,7 @@ impl Action {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct Ship {
     facing: Direction,
     north: i32,  // negative is south
@@ -111,42 +131,55 @@ impl Ship {
             }
             _ => {
                 // Rotation does not change position
-                self.facing = self.facing.apply(&action);
+                self.facing = self.facing.apply(action);
             },
         }
     }
 
-    fn follow_q1(actions: &[Action]) -> Ship {
+    #[cfg(test)]
+    fn follow_q1(actions: &[Action]) -> Result<Ship, String> {
+        Ok(Self::trace_q1(actions)?.0)
+    }
+
+    // Q1 navigation, recording every step on the way.
+    fn trace_q1(actions: &[Action]) -> Result<(Ship, Vec<Step>), String> {
         let mut ship = Ship::new();
+        let mut steps = Vec::with_capacity(actions.len());
 
-        for (i, a) in actions.iter().enumerate() {
-            println!("i={} ship={:?} + {:?}", i, ship, a);
+        for (idx, a) in actions.iter().enumerate() {
+            a.check_right_angle(idx)?;
+            let start = ship;
             ship.apply(a);
+            steps.push(Step { action: *a, start, end: ship, waypoint: None });
         }
 
-        println!("  ship now {:?}", ship);
-        ship
+        Ok((ship, steps))
+    }
+
+    #[cfg(test)]
+    fn follow_q2(actions: &[Action]) -> Result<Ship, String> {
+        Ok(Self::trace_q2(actions)?.0)
     }
 
-    fn follow_q2(actions: &[Action]) -> Ship {
+    // Q2 navigation, recording every step and the waypoint after it.
+    fn trace_q2(actions: &[Action]) -> Result<(Ship, Vec<Step>), String> {
         let mut ship = Ship::new();
         let mut waypoint = Waypoint::new();
+        let mut steps = Vec::with_capacity(actions.len());
 
-        for (i, a) in actions.iter().enumerate() {
-            println!("i={} ship={:?} wayp={:?} + {:?}", i, ship, waypoint, a);
-
+        for (idx, a) in actions.iter().enumerate() {
+            a.check_right_angle(idx)?;
+            let start = ship;
             if a.action == Direction::F {
-                println!("move {} times n={:?} e={:?}!", a.value, waypoint.north, waypoint.east);
                 ship.north += waypoint.north * a.value;
                 ship.east += waypoint.east * a.value;
             } else {
                 waypoint.apply(a);
             }
+            steps.push(Step { action: *a, start, end: ship, waypoint: Some(waypoint) });
         }
 
-        println!("  ship now {:?}", ship);
-        println!("  waypoint now {:?}", waypoint);
-        ship
+        Ok((ship, steps))
     }
 
     fn travelled(&self) -> i32 {
@@ -154,7 +187,7 @@ impl Ship {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct Waypoint {
     north: i32,  // negative is south
     east: i32,  // negative is west
@@ -172,11 +205,11 @@ impl Waypoint {
         let east = self.east;
         let north = self.north;
         if direction == Direction::L {
-            self.east = north * -1;
+            self.east = -north;
             self.north = east;
         } else if direction == Direction::R {
             self.east = north;
-            self.north = east * -1;
+            self.north = -east;
         }
     }
 
@@ -190,7 +223,7 @@ impl Waypoint {
             F => (),  // waypoint is relative to the ship
             _ => {
                 // Rotate around the ship
-                let mut n = action.value / 90;
+                let mut n = (action.value / 90).rem_euclid(4);
                 while n > 0 {
                     self.rotate(action.action);
                     n -= 1;
@@ -203,12 +236,34 @@ impl Waypoint {
 fn main() {
     let path = env::args().nth(1).expect("please supply a path");
     let contents = Action::read(&path).expect("no content");
+    let option = |name: &str| env::args()
+        .find_map(|a| a.strip_prefix(&format!("--{}=", name)).map(|v| v.to_string()));
 
-    let ship = Ship::follow_q1(&contents);
+    if env::args().any(|a| a == "--continuous") {
+        let ship = heading::FreeShip::follow_q1(&contents);
+        println!("Q1 ship distance: {} (at {:.3} east, {:.3} north)", ship.travelled(), ship.east, ship.north);
+        let ship = heading::FreeShip::follow_q2(&contents);
+        println!("Q2 ship distance: {} (at {:.3} east, {:.3} north)", ship.travelled(), ship.east, ship.north);
+        return;
+    }
+
+    let (ship, steps_q1) = Ship::trace_q1(&contents).expect("invalid rotation");
     println!("Q1 ship distance: {}", ship.travelled());
 
-    let ship = Ship::follow_q2(&contents);
+    let (ship, steps_q2) = Ship::trace_q2(&contents).expect("invalid rotation");
     println!("Q2 ship distance: {}", ship.travelled());
+
+    let steps = match option("part").as_deref() {
+        Some("1") => steps_q1,
+        None | Some("2") => steps_q2,
+        Some(other) => panic!("unknown part {}", other),
+    };
+    if let Some(csv_path) = option("csv") {
+        std::fs::write(&csv_path, trace::to_csv(&steps)).expect("cannot write csv");
+    }
+    if let Some(svg_path) = option("svg") {
+        std::fs::write(&svg_path, trace::to_svg(&steps)).expect("cannot write svg");
+    }
 }
 
 #[cfg(test)]
@@ -221,10 +276,10 @@ mod tests {
     case(&"day_12/input.txt", 403, -187),
     )]
     fn test_follow_q1(path: &str, exp_east: i32, exp_north: i32) {
-        let contents = Action::read(&path);
+        let contents = Action::read(path);
         assert!(contents.is_ok());
         let contents = contents.unwrap();
-        let ship = Ship::follow_q1(&contents);
+        let ship = Ship::follow_q1(&contents).unwrap();
         assert_eq!(ship.east, exp_east);
         assert_eq!(ship.north, exp_north);
     }
@@ -234,10 +289,10 @@ mod tests {
     case(&"day_12/input.txt", -29191, 12822),
     )]
     fn test_follow_q2(path: &str, exp_east: i32, exp_north: i32) {
-        let contents = Action::read(&path);
+        let contents = Action::read(path);
         assert!(contents.is_ok());
         let contents = contents.unwrap();
-        let ship = Ship::follow_q2(&contents);
+        let ship = Ship::follow_q2(&contents).unwrap();
         assert_eq!(ship.east, exp_east);
         assert_eq!(ship.north, exp_north);
     }
@@ -263,10 +318,23 @@ mod tests {
     case(Direction::E, Action{action: Direction::R, value: 180}, Direction::W),
     case(Direction::E, Action{action: Direction::R, value: 270}, Direction::N),
     case(Direction::E, Action{action: Direction::R, value: 360}, Direction::E),
+    case(Direction::E, Action{action: Direction::R, value: 450}, Direction::S),
+    case(Direction::E, Action{action: Direction::L, value: 450}, Direction::N),
     )]
     fn test_apply_direction(ship: Direction, action: Action, exp_ship: Direction) {
         let new_ship = ship.apply(&action);
         println!("{:?} + {:?} => {:?}", ship, action, new_ship);
         assert_eq!(new_ship, exp_ship);
     }
+
+    #[rstest(action, value,
+    case(Direction::L, 45),
+    case(Direction::R, 100),
+    )]
+    fn test_integer_mode_rejects_angles(action: Direction, value: i32) {
+        let actions = vec![Action{action: Direction::F, value: 10}, Action{action, value}];
+        let err = Ship::follow_q1(&actions).unwrap_err();
+        assert!(err.starts_with("action 2:"), "{}", err);
+        assert!(Ship::follow_q2(&actions).is_err());
+    }
 }
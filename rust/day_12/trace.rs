@@ -0,0 +1,127 @@
+use super::{Action, Ship, Waypoint};
+
+// One action and what it did to the ship (and to the waypoint for Q2).
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    pub action: Action,
+    pub start: Ship,
+    pub end: Ship,
+    pub waypoint: Option<Waypoint>,  // after the action
+}
+
+pub fn to_csv(steps: &[Step]) -> String {
+    let mut rv = String::from("index,action,value,start_east,start_north,end_east,end_north,facing,waypoint_east,waypoint_north\n");
+    for (idx, s) in steps.iter().enumerate() {
+        let (w_east, w_north) = match s.waypoint {
+            Some(w) => (w.east.to_string(), w.north.to_string()),
+            None => (String::new(), String::new()),
+        };
+        rv += &format!("{},{:?},{},{},{},{},{},{:?},{},{}\n",
+            idx, s.action.action, s.action.value,
+            s.start.east, s.start.north,
+            s.end.east, s.end.north,
+            s.end.facing,
+            w_east, w_north,
+        );
+    }
+    rv
+}
+
+// Path of the ship as a polyline, north is up. For Q2 each waypoint is drawn
+// as a line from where the ship is to where the waypoint points.
+pub fn to_svg(steps: &[Step]) -> String {
+    let mut points = vec![(0, 0)];
+    points.extend(steps.iter().map(|s| (s.end.east, s.end.north)));
+    let vectors: Vec<((i32, i32), (i32, i32))> = steps.iter()
+        .filter_map(|s| s.waypoint.map(|w| (
+            (s.end.east, s.end.north),
+            (s.end.east + w.east, s.end.north + w.north),
+        )))
+        .collect();
+
+    let all = points.iter().chain(vectors.iter().map(|(_, to)| to));
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (0, 0, 0, 0);
+    for &(x, y) in all {
+        min_x = min_x.min(x);
+        max_x = max_x.max(x);
+        min_y = min_y.min(-y);
+        max_y = max_y.max(-y);
+    }
+    let margin = 10;
+
+    let mut rv = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n",
+        min_x - margin, min_y - margin, max_x - min_x + 2 * margin, max_y - min_y + 2 * margin,
+    );
+    for (from, to) in &vectors {
+        rv += &format!(
+            "  <line class=\"waypoint\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"orange\" />\n",
+            from.0, -from.1, to.0, -to.1,
+        );
+    }
+    let points: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", x, -y)).collect();
+    rv += &format!(
+        "  <polyline class=\"ship\" points=\"{}\" fill=\"none\" stroke=\"blue\" />\n",
+        points.join(" "),
+    );
+    rv += "</svg>\n";
+    rv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> Vec<Action> {
+        Action::read("day_12/test_1.txt").unwrap()
+    }
+
+    #[test]
+    fn test_trace_q1() {
+        let (ship, steps) = Ship::trace_q1(&example()).unwrap();
+        assert_eq!(steps.len(), 5);
+        let ends: Vec<(i32, i32)> = steps.iter().map(|s| (s.end.east, s.end.north)).collect();
+        assert_eq!(ends, vec![(10, 0), (10, 3), (17, 3), (17, 3), (17, -8)]);
+        assert_eq!((steps[4].start.east, steps[4].start.north), (17, 3));
+        assert_eq!((ship.east, ship.north), (17, -8));
+        assert!(steps.iter().all(|s| s.waypoint.is_none()));
+    }
+
+    #[test]
+    fn test_trace_q2() {
+        let (_, steps) = Ship::trace_q2(&example()).unwrap();
+        let waypoints: Vec<(i32, i32)> = steps.iter()
+            .map(|s| s.waypoint.map(|w| (w.east, w.north)).unwrap())
+            .collect();
+        assert_eq!(waypoints, vec![(10, 1), (10, 4), (10, 4), (4, -10), (4, -10)]);
+        let ends: Vec<(i32, i32)> = steps.iter().map(|s| (s.end.east, s.end.north)).collect();
+        assert_eq!(ends, vec![(100, 10), (100, 10), (170, 38), (170, 38), (214, -72)]);
+    }
+
+    #[test]
+    fn test_csv() {
+        let (_, steps) = Ship::trace_q2(&example()).unwrap();
+        let csv = to_csv(&steps);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[1], "0,F,10,0,0,100,10,E,10,1");
+        assert_eq!(lines[4], "3,R,90,170,38,170,38,E,4,-10");
+
+        let (_, steps) = Ship::trace_q1(&example()).unwrap();
+        assert_eq!(to_csv(&steps).lines().nth(4), Some("3,R,90,17,3,17,3,S,,"));
+    }
+
+    #[test]
+    fn test_svg() {
+        let (_, steps) = Ship::trace_q1(&example()).unwrap();
+        let svg = to_svg(&steps);
+        assert!(svg.contains("viewBox=\"-10 -13 37 31\""), "{}", svg);
+        assert!(svg.contains("points=\"0,0 10,0 10,-3 17,-3 17,-3 17,8\""));
+        assert!(!svg.contains("<line"));
+
+        let (_, steps) = Ship::trace_q2(&example()).unwrap();
+        let svg = to_svg(&steps);
+        assert_eq!(svg.matches("<line").count(), 5);
+        assert!(svg.contains("x1=\"214\" y1=\"72\" x2=\"218\" y2=\"82\""));
+    }
+}
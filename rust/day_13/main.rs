@@ -1,93 +1,127 @@
-use std::{io, env};
-use std::fs::File;
-use std::io::{BufReader, BufRead};
-use std::collections::HashMap;
+use std::{io, env, fs};
+use std::collections::HashSet;
+
+mod number;
+mod schedule;
+
+use number::{Congruence, CrtError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bus {
+    name: String,  // the id when not named
+    id: usize,
+}
 
 #[derive(Debug)]
 struct Prediction {
     when: usize,
-    timetable: Vec<Option<usize>>
+    timetable: Vec<Option<Bus>>
+}
+
+fn invalid(line_no: usize, msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line_no, msg))
+}
+
+impl Bus {
+    // `x`, `<id>` or `<name>=<id>`
+    fn from_string(entry: &str, line_no: usize) -> io::Result<Option<Bus>> {
+        if entry == "x" {
+            return Ok(None);
+        }
+        let (name, id) = match entry.split_once('=') {
+            Some((name, id)) => (name.trim(), id.trim()),
+            None => (entry, entry),
+        };
+        if name.is_empty() {
+            return Err(invalid(line_no, &format!("empty bus name in {:?}", entry)));
+        }
+        match id.parse() {
+            Ok(0) | Err(_) => Err(invalid(line_no, &format!("invalid bus id in {:?}", entry))),
+            Ok(id) => Ok(Some(Bus { name: name.to_string(), id })),
+        }
+    }
 }
 
 impl Prediction {
-    fn read(path: &str) -> io::Result<Prediction> {
-        let file = File::open(path)?;
-        let br = BufReader::new(file);
-        let mut when = 0;
-        let mut timetable = Vec::new();
+    // The departure time is the first line, written @939 or, as in the puzzle
+    // input, a bare number followed by the timetable. Without it the time is 0.
+    // The timetable can be split over several lines, so one starting with a
+    // single bus needs a time line first (@0). Empty lines and lines starting
+    // with # are ignored.
+    fn from_string(content: &str) -> io::Result<Prediction> {
+        let lines: Vec<(usize, &str)> = content.lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.trim()))
+            .filter(|(_, l)| !l.is_empty() && !l.starts_with('#'))
+            .collect();
 
-        for (i, line) in br.lines().enumerate() {
-            let line = line?;
+        let mut when = 0;
+        let mut timetable_lines = &lines[..];
+        if let Some(&(line_no, first)) = lines.first() {
+            let time = match first.strip_prefix('@') {
+                Some(time) => Some(time.trim()),
+                None if lines.len() > 1 && first.chars().all(|c| c.is_ascii_digit()) => Some(first),
+                None => None,
+            };
+            if let Some(time) = time {
+                when = time.parse().map_err(|_| invalid(line_no, &format!("invalid departure time {:?}", time)))?;
+                timetable_lines = &lines[1..];
+            }
+        }
 
-            if i == 0 {
-                when = line.parse().unwrap();
-            } else {
-                timetable = line.split(",")
-                    .map(|c| if let Ok(v) = c.parse() { Some(v) } else {None})
-                    .collect();
+        let mut timetable = Vec::new();
+        let mut names = HashSet::new();
+        for &(line_no, line) in timetable_lines {
+            for entry in line.trim_end_matches(',').split(',') {
+                let bus = Bus::from_string(entry.trim(), line_no)?;
+                if let Some(bus) = &bus {
+                    if !names.insert(bus.name.clone()) {
+                        return Err(invalid(line_no, &format!("bus {} is listed twice", bus.name)));
+                    }
+                }
+                timetable.push(bus);
             }
         }
+        if names.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "no bus in the timetable"));
+        }
         Ok(Prediction { when, timetable })
     }
 
-    fn build_wait(&self) -> HashMap<usize, usize> {
-        let mut rv = HashMap::new();
-        for bus in &self.timetable {
-            if let Some(bus) = bus {
-                rv.insert(bus.clone(), (self.when / bus + 1) * bus);
-            }
-        }
-        rv
+    fn read(path: &str) -> io::Result<Prediction> {
+        Self::from_string(&fs::read_to_string(path)?)
+    }
+
+    fn buses(&self) -> impl Iterator<Item = &Bus> {
+        self.timetable.iter().flatten()
     }
 
     // return: smallest bus, wait for that bus
     fn earliest_bus(&self) -> (usize, usize) {
-        let wait = self.build_wait();
-        println!("built wait: {:?}", wait);
-        // that didn't work so well, it used the key
-        // let smallest = wait.iter().min_by(|&(_, v)| v).unwrap();
-        // I'm lazy for now
-        let smallest_wait = wait.values().min().unwrap();
-        let smallest_bus: Vec<&usize> = wait.iter()
-            .filter(|&(_, v)| v == smallest_wait)
-            .map(|(b, _)| b)
-            .collect();
-        (*smallest_bus[0], (smallest_wait - self.when))
+        let (time, bus) = self.departures(self.when, 1)[0];
+        (bus.id, time - self.when)
     }
 
     fn build_sync_table(&self) -> Vec<usize> {
         // x => 1 because the bus can go whenever.
-        self.timetable.iter().map(|v| v.unwrap_or(1)).collect()
-    }
-
-    fn earliest_synchronous(&self) -> usize {
-        let contents = self.build_sync_table();
-        let mut rv = vec!();
-        let mut i = 0;
-        let mut t = contents[0];
-        let mut inc = contents[0];
-
-        while rv.len() < contents.len() - 1 {
-            let idx = rv.len() + 1;
-            let target = contents[idx];
-            if (t + 1) % target == 0 {
-                println!("i={} rv={:?} + {} for {}", i, rv, t + 1, contents[idx]);
-                rv.push(t);
-                inc *= contents[idx];
-                t += 1;
-            } else {
-                t += inc;
-            }
-            i += 1;
-        }
-        println!("Found in {} iterations", i);
-        t - rv.len()
+        self.timetable.iter().map(|v| v.as_ref().map_or(1, |b| b.id)).collect()
+    }
+
+    // Bus at offset i with id b has to leave at t + i, so t = -i (mod b).
+    fn earliest_synchronous(&self) -> Result<i128, CrtError> {
+        let congruences: Vec<Congruence> = self.build_sync_table().iter()
+            .enumerate()
+            .map(|(i, &bus)| Congruence::new(-(i as i128), bus as i128))
+            .collect();
+        Ok(number::crt(&congruences)?.residue)
     }
 }
 
 fn main() {
     let path = env::args().nth(1).expect("please supply a path");
     let contents = Prediction::read(&path).expect("no content");
+    let option = |name: &str| env::args()
+        .find_map(|a| a.strip_prefix(&format!("--{}=", name)).map(|v| v.to_string()));
 
     let (bus, wait) = contents.earliest_bus();
     println!("Q1 need to wait {} min for bus {}: {}",
@@ -96,8 +130,36 @@ fn main() {
         wait * bus,
     );
 
-    let earliest = contents.earliest_synchronous();
-    println!("Q2: {}", earliest);
+    match contents.earliest_synchronous() {
+        Ok(earliest) => println!("Q2: {}", earliest),
+        Err(e) => println!("Q2: {}", e),
+    }
+
+    match contents.period() {
+        Some(period) => println!("The timetable repeats every {} min", period),
+        None => println!("The timetable period does not fit in 128 bits"),
+    }
+
+    if let Some(n) = option("next") {
+        let n = n.parse().expect("need a number of departures");
+        for (time, bus) in contents.departures(contents.when, n) {
+            println!("{} bus {} ({})", time, bus.name, bus.id);
+        }
+    }
+
+    // --align=name:offset,name:offset
+    if let Some(spec) = option("align") {
+        let subset: Vec<(&str, i128)> = spec.split(',')
+            .map(|s| {
+                let (name, offset) = s.split_once(':').unwrap_or((s, "0"));
+                (name, offset.parse().expect("need a numeric offset"))
+            })
+            .collect();
+        match contents.aligned(contents.when as i128, &subset) {
+            Ok(t) => println!("Aligned at {}", t),
+            Err(e) => println!("Cannot align: {}", e),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -109,7 +171,7 @@ mod tests {
     case(&"day_13/test_1.txt", (59, 5)),
     )]
     fn test_earliest_bus(path: &str, exp_route: (usize, usize)) {
-        let contents = Prediction::read(&path);
+        let contents = Prediction::read(path);
         assert!(contents.is_ok());
         let contents = contents.unwrap();
         println!("Loaded {:?}", contents);
@@ -125,11 +187,70 @@ mod tests {
     case(vec!(67,7,1,59,61), 1261476),
     case(vec!(1789,37,47,1889), 1202161486),
     )]
-    fn test_brute_q2(data: Vec<usize>, exp_ts: usize) {
+    fn test_brute_q2(data: Vec<usize>, exp_ts: i128) {
         let contents = Prediction {
             when: 0,  // ignored
-            timetable: data.iter().map(|v| Some(*v)).collect(),
+            timetable: data.iter().map(|v| Some(Bus { name: v.to_string(), id: *v })).collect(),
         };
-        assert_eq!(contents.earliest_synchronous(), exp_ts);
+        assert_eq!(contents.earliest_synchronous(), Ok(exp_ts));
+    }
+
+    #[rstest(data, exp,
+    case(vec!(Some(4), None, Some(6)), Ok(4)),
+    case(vec!(Some(4), Some(6)), Err(())),
+    case(vec!(Some(6), None, Some(4)), Ok(6)),
+    )]
+    fn test_synchronous_not_coprime(data: Vec<Option<usize>>, exp: Result<i128, ()>) {
+        let timetable = data.iter()
+            .map(|v| v.map(|id| Bus { name: id.to_string(), id }))
+            .collect();
+        let contents = Prediction { when: 0, timetable };
+        let rv = contents.earliest_synchronous();
+        assert_eq!(rv.map_err(|_| ()), exp);
+    }
+
+    #[test]
+    fn test_synchronous_input() {
+        let contents = Prediction::read("day_13/input.txt").unwrap();
+        assert_eq!(contents.earliest_synchronous(), Ok(213890632230818));
+    }
+
+    #[rstest(input, exp_when, exp_timetable,
+    case("939\n7,13,x,x,59,x,31,19", 939, "7,13,x,x,59,x,31,19"),
+    case("7,13,x", 0, "7,13,x"),
+    case("17", 0, "17"),
+    case("# departure\n10\n\nred=7,x,\nblue = 13\n", 10, "red,x,blue"),
+    case("10\n7,x,\n13", 10, "7,x,13"),
+    case("10\n7,x\n13", 10, "7,x,13"),
+    // a bare first line is always the departure time
+    case("939\n17", 939, "17"),
+    case("7\n13\n19", 7, "13,19"),
+    case("@0\n7\n13", 0, "7,13"),
+    case("@10\n7\n13", 10, "7,13"),
+    case("10\nx\n13", 10, "x,13"),
+    )]
+    fn test_from_string(input: &str, exp_when: usize, exp_timetable: &str) {
+        let contents = Prediction::from_string(input).unwrap();
+        assert_eq!(contents.when, exp_when);
+        let names: Vec<String> = contents.timetable.iter()
+            .map(|b| b.as_ref().map_or("x".to_string(), |b| b.name.clone()))
+            .collect();
+        assert_eq!(names.join(","), exp_timetable);
+    }
+
+    #[rstest(input,
+    case(""),
+    case("939\nx,x"),
+    case("@\n7,13"),
+    case("@abc\n7,13"),
+    case("@10"),
+    case("939\n7,0"),
+    case("939\n7,y"),
+    case("939\n=7"),
+    case("939\na=7,a=9"),
+    case("939\n7,7"),
+    )]
+    fn test_from_string_invalid(input: &str) {
+        assert!(Prediction::from_string(input).is_err());
     }
 }
@@ -0,0 +1,173 @@
+use std::fmt;
+
+// (g, x, y) such that a * x + b * y = g = gcd(a, b), with g >= 0.
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+    if old_r < 0 {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+// x in 0..m with a * x = 1 (mod m), None when a and m are not coprime.
+pub fn mod_inverse(a: i128, m: i128) -> Option<i128> {
+    let (g, x, _) = extended_gcd(a.rem_euclid(m), m);
+    if g != 1 {
+        return None;
+    }
+    Some(x.rem_euclid(m))
+}
+
+// None when it does not fit.
+pub fn lcm(a: i128, b: i128) -> Option<i128> {
+    let (g, _, _) = extended_gcd(a, b);
+    if g == 0 {
+        return Some(0);
+    }
+    (a / g).checked_mul(b).map(i128::abs)
+}
+
+// x = residue (mod modulus)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Congruence {
+    pub residue: i128,
+    pub modulus: i128,
+}
+
+impl Congruence {
+    pub fn new(residue: i128, modulus: i128) -> Congruence {
+        Congruence { residue: residue.rem_euclid(modulus), modulus }
+    }
+
+    // Single congruence satisfying both, modulo their lcm.
+    // The moduli do not need to be coprime.
+    pub fn merge(&self, other: &Congruence) -> Result<Congruence, CrtError> {
+        let (g, _, _) = extended_gcd(self.modulus, other.modulus);
+        let diff = other.residue - self.residue;
+        if diff % g != 0 {
+            return Err(CrtError::NoSolution(*self, *other));
+        }
+        let m = other.modulus / g;
+        // self.residue + self.modulus * k = other.residue (mod other.modulus)
+        let inv = mod_inverse(self.modulus / g, m).expect("coprime after dividing by gcd");
+        let k = (diff / g).rem_euclid(m)
+            .checked_mul(inv)
+            .ok_or(CrtError::Overflow)?
+            .rem_euclid(m);
+        let modulus = (self.modulus / g).checked_mul(other.modulus).ok_or(CrtError::Overflow)?;
+        let residue = self.modulus.checked_mul(k)
+            .and_then(|v| v.checked_add(self.residue))
+            .ok_or(CrtError::Overflow)?;
+        Ok(Congruence::new(residue, modulus))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrtError {
+    // the congruences merged so far and the one that contradicts them
+    NoSolution(Congruence, Congruence),
+    Overflow,
+}
+
+impl fmt::Display for CrtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CrtError::NoSolution(a, b) => write!(
+                f, "no solution: x = {} (mod {}) and x = {} (mod {})",
+                a.residue, a.modulus, b.residue, b.modulus,
+            ),
+            CrtError::Overflow => write!(f, "overflow"),
+        }
+    }
+}
+
+// Generalised chinese remainder theorem: merge every congruence into one.
+pub fn crt(congruences: &[Congruence]) -> Result<Congruence, CrtError> {
+    congruences.iter()
+        .try_fold(Congruence::new(0, 1), |acc, c| acc.merge(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(a, b, exp_g,
+    case(240, 46, 2),
+    case(46, 240, 2),
+    case(17, 5, 1),
+    case(0, 5, 5),
+    case(5, 0, 5),
+    case(-12, 18, 6),
+    case(12, -18, 6),
+    )]
+    fn test_extended_gcd(a: i128, b: i128, exp_g: i128) {
+        let (g, x, y) = extended_gcd(a, b);
+        assert_eq!(g, exp_g);
+        assert_eq!(a * x + b * y, g);
+    }
+
+    #[rstest(a, b, exp,
+    case(4, 6, Some(12)),
+    case(7, 13, Some(91)),
+    case(0, 5, Some(0)),
+    case(i128::MAX, i128::MAX - 1, None),
+    )]
+    fn test_lcm(a: i128, b: i128, exp: Option<i128>) {
+        assert_eq!(lcm(a, b), exp);
+    }
+
+    #[rstest(a, m, exp,
+    case(3, 11, Some(4)),
+    case(10, 17, Some(12)),
+    case(-3, 11, Some(7)),
+    case(6, 9, None),
+    case(1, 1, Some(0)),
+    )]
+    fn test_mod_inverse(a: i128, m: i128, exp: Option<i128>) {
+        assert_eq!(mod_inverse(a, m), exp);
+    }
+
+    #[rstest(input, exp,
+    case(&[(2, 3), (3, 5), (2, 7)], Some((23, 105))),
+    // not coprime, but compatible
+    case(&[(2, 4), (4, 6)], Some((10, 12))),
+    case(&[(3, 10), (1, 4), (8, 15)], Some((53, 60))),
+    case(&[(0, 4), (5, 6)], None),
+    case(&[], Some((0, 1))),
+    )]
+    fn test_crt(input: &[(i128, i128)], exp: Option<(i128, i128)>) {
+        let congruences: Vec<Congruence> = input.iter().map(|&(r, m)| Congruence::new(r, m)).collect();
+        match (crt(&congruences), exp) {
+            (Ok(c), Some((r, m))) => assert_eq!(c, Congruence::new(r, m)),
+            (Err(CrtError::NoSolution(_, _)), None) => (),
+            (rv, exp) => panic!("got {:?}, expected {:?}", rv, exp),
+        }
+    }
+
+    #[test]
+    fn test_crt_reports_conflict() {
+        let congruences = [Congruence::new(1, 3), Congruence::new(0, 4), Congruence::new(5, 6)];
+        assert_eq!(
+            crt(&congruences),
+            Err(CrtError::NoSolution(Congruence::new(4, 12), Congruence::new(5, 6))),
+        );
+    }
+
+    #[test]
+    fn test_crt_overflow() {
+        let big = (1i128 << 100) + 1;
+        let congruences = [Congruence::new(1, big), Congruence::new(2, big + 2)];
+        assert_eq!(crt(&congruences), Err(CrtError::Overflow));
+    }
+}
@@ -0,0 +1,98 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use super::{Bus, Prediction};
+use super::number::{self, Congruence};
+
+impl Prediction {
+    // Next `n` departures at or after `after`, ordered by time then by timetable position.
+    pub fn departures(&self, after: usize, n: usize) -> Vec<(usize, &Bus)> {
+        let buses: Vec<&Bus> = self.buses().collect();
+        let mut next: BinaryHeap<Reverse<(usize, usize)>> = buses.iter()
+            .enumerate()
+            .map(|(idx, b)| Reverse((after.div_ceil(b.id) * b.id, idx)))
+            .collect();
+
+        let mut rv = Vec::with_capacity(n);
+        while rv.len() < n {
+            let Reverse((time, idx)) = match next.pop() {
+                Some(d) => d,
+                None => break,
+            };
+            rv.push((time, buses[idx]));
+            next.push(Reverse((time + buses[idx].id, idx)));
+        }
+        rv
+    }
+
+    fn bus(&self, name: &str) -> Option<&Bus> {
+        self.buses().find(|b| b.name == name)
+    }
+
+    // First time t >= after where each named bus leaves at t + its offset.
+    pub fn aligned(&self, after: i128, subset: &[(&str, i128)]) -> Result<i128, String> {
+        let mut congruences = Vec::with_capacity(subset.len());
+        for &(name, offset) in subset {
+            let bus = self.bus(name).ok_or_else(|| format!("unknown bus {}", name))?;
+            congruences.push(Congruence::new(-offset, bus.id as i128));
+        }
+        let c = number::crt(&congruences).map_err(|e| e.to_string())?;
+        // smallest residue + k * modulus which is not before `after`
+        let behind = (after - c.residue).max(0);
+        let k = (behind + c.modulus - 1) / c.modulus;
+        k.checked_mul(c.modulus)
+            .and_then(|v| v.checked_add(c.residue))
+            .ok_or_else(|| "overflow".to_string())
+    }
+
+    // Time after which every bus departs at the same point of its cycle again.
+    pub fn period(&self) -> Option<i128> {
+        self.buses().try_fold(1, |acc, b| number::lcm(acc, b.id as i128))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn example() -> Prediction {
+        Prediction::read("day_13/test_1.txt").unwrap()
+    }
+
+    #[test]
+    fn test_departures() {
+        let contents = example();
+        let rv: Vec<(usize, usize)> = contents.departures(939, 5).iter()
+            .map(|&(t, b)| (t, b.id))
+            .collect();
+        assert_eq!(rv, vec![(944, 59), (945, 7), (949, 13), (950, 19), (952, 7)]);
+    }
+
+    #[test]
+    fn test_departures_at_time() {
+        let contents = Prediction::from_string("a=2,b=3").unwrap();
+        let rv: Vec<(usize, &str)> = contents.departures(6, 4).iter()
+            .map(|&(t, b)| (t, b.name.as_str()))
+            .collect();
+        // both leave at 6, in timetable order
+        assert_eq!(rv, vec![(6, "a"), (6, "b"), (8, "a"), (9, "b")]);
+    }
+
+    #[rstest(after, subset, exp,
+    case(0, &[("7", 0), ("13", 1), ("59", 4), ("31", 6), ("19", 7)], Ok(1068781)),
+    case(0, &[("7", 0), ("13", 1)], Ok(77)),
+    case(100, &[("7", 0), ("13", 1)], Ok(168)),
+    case(168, &[("7", 0), ("13", 1)], Ok(168)),
+    case(0, &[("7", 0), ("7", 1)], Err("no solution: x = 0 (mod 7) and x = 6 (mod 7)")),
+    case(0, &[("8", 0)], Err("unknown bus 8")),
+    )]
+    fn test_aligned(after: i128, subset: &[(&str, i128)], exp: Result<i128, &str>) {
+        assert_eq!(example().aligned(after, subset), exp.map_err(|e| e.to_string()));
+    }
+
+    #[test]
+    fn test_period() {
+        assert_eq!(example().period(), Some(7 * 13 * 59 * 31 * 19));
+        assert_eq!(Prediction::from_string("4,6").unwrap().period(), Some(12));
+    }
+}
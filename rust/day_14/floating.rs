@@ -0,0 +1,140 @@
+use super::{Op, Operation};
+
+// A set of addresses: every floating bit can be 0 or 1, the other bits are fixed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pattern {
+    pub fixed: usize,  // floating bits are always 0 here
+    pub floating: usize,
+}
+
+impl Pattern {
+    pub fn new(fixed: usize, floating: usize) -> Pattern {
+        Pattern { fixed: fixed & !floating, floating }
+    }
+
+    // Addresses written by the q2 decoder: 1 bits of the mask set, X bits floating.
+    fn from_mask(mask: &Operation, addr: usize) -> Pattern {
+        Pattern::new((addr & !mask.a) | mask.b, mask.a)
+    }
+
+    pub fn count(&self) -> u128 {
+        1 << self.floating.count_ones()
+    }
+
+    fn overlaps(&self, other: &Pattern) -> bool {
+        let both_fixed = !(self.floating | other.floating);
+        (self.fixed ^ other.fixed) & both_fixed == 0
+    }
+
+    // Disjoint patterns covering self without other.
+    pub fn subtract(&self, other: &Pattern) -> Vec<Pattern> {
+        if !self.overlaps(other) {
+            return vec![*self];
+        }
+        let mut rv = Vec::new();
+        let mut rest = *self;
+        // bits we float but the other one fixes: the half with the opposite
+        // value is outside the other pattern, keep going with the other half
+        let mut split = self.floating & !other.floating;
+        while split != 0 {
+            let bit = split & split.wrapping_neg();
+            split &= !bit;
+            rest.floating &= !bit;
+            rv.push(Pattern::new(rest.fixed | (!other.fixed & bit), rest.floating));
+            rest.fixed |= other.fixed & bit;
+        }
+        // what is left is inside other
+        rv
+    }
+}
+
+// Writes kept as disjoint patterns, so nothing has to be expanded.
+#[derive(Debug, Default)]
+pub struct FloatingMemory {
+    writes: Vec<(Pattern, usize)>,
+}
+
+impl FloatingMemory {
+    pub fn write(&mut self, pattern: Pattern, value: usize) {
+        let mut writes = Vec::with_capacity(self.writes.len() + 1);
+        for (p, v) in &self.writes {
+            writes.extend(p.subtract(&pattern).into_iter().map(|p| (p, *v)));
+        }
+        writes.push((pattern, value));
+        self.writes = writes;
+    }
+
+    // Writes are disjoint, so at most 2^64 addresses of 64 bits values: fits in u128.
+    pub fn sum(&self) -> u128 {
+        self.writes.iter().map(|(p, v)| p.count() * *v as u128).sum()
+    }
+
+    pub fn writes(&self) -> &[(Pattern, usize)] {
+        &self.writes
+    }
+
+    pub fn run(operations: &[Operation], bits: u32) -> FloatingMemory {
+        let mut memory = FloatingMemory::default();
+        let mut last_mask = Operation::empty_mask(bits);
+
+        for op in operations {
+            if op.op == Op::NewMask {
+                last_mask = op.clone();
+            } else {
+                memory.write(Pattern::from_mask(&last_mask, op.a), op.b);
+            }
+        }
+        memory
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::DEFAULT_BITS;
+    use rstest::rstest;
+
+    fn expand(p: &Pattern) -> Vec<usize> {
+        let mut rv = vec![p.fixed];
+        for bit in 0..usize::BITS {
+            if p.floating & (1 << bit) != 0 {
+                rv = rv.iter().flat_map(|a| vec![*a, a | (1 << bit)]).collect();
+            }
+        }
+        rv.sort_unstable();
+        rv
+    }
+
+    #[rstest(a, b,
+    case(Pattern::new(0b0000, 0b1111), Pattern::new(0b0101, 0b0000)),
+    case(Pattern::new(0b0000, 0b1100), Pattern::new(0b0001, 0b0011)),
+    case(Pattern::new(0b0001, 0b1100), Pattern::new(0b0100, 0b0010)),
+    case(Pattern::new(0b0001, 0b0000), Pattern::new(0b0000, 0b0001)),
+    case(Pattern::new(0b1000, 0b0110), Pattern::new(0b0000, 0b0110)),
+    )]
+    fn test_subtract(a: Pattern, b: Pattern) {
+        let pieces = a.subtract(&b);
+        let mut rv: Vec<usize> = pieces.iter().flat_map(expand).collect();
+        rv.sort_unstable();
+        let remove = expand(&b);
+        let exp: Vec<usize> = expand(&a).into_iter().filter(|x| !remove.contains(x)).collect();
+        // disjoint pieces: no address is listed twice
+        assert_eq!(rv, exp);
+    }
+
+    #[rstest(path, case("day_14/test_2.txt"), case("day_14/input.txt"))]
+    fn test_agrees_with_expansion(path: &str) {
+        let contents = Operation::read(path, DEFAULT_BITS).unwrap();
+        let exp = Operation::execute_q2(&contents, DEFAULT_BITS);
+        assert_eq!(FloatingMemory::run(&contents, DEFAULT_BITS).sum(), exp);
+    }
+
+    #[test]
+    fn test_many_floating_bits() {
+        // would be 2^34 addresses each when expanded
+        let contents = Operation::read("day_14/test_1.txt", DEFAULT_BITS).unwrap();
+        let memory = FloatingMemory::run(&contents, DEFAULT_BITS).sum();
+        // mem[8] = 0 overwrites mem[8] = 11, the kept bit 1 keeps mem[7] = 101 apart
+        assert_eq!(memory, 101 << 34);
+    }
+}
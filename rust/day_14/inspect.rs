@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use super::{Op, Operation};
+use super::floating::FloatingMemory;
+
+fn binary(v: usize, bits: u32) -> String {
+    format!("{:0width$b}", v, width = bits as usize)
+}
+
+// Effective bitmasks of every `mask =` line:
+// q1 keeps the value bits under `and` then sets `or`,
+// q2 sets `or` on the address and lets `floating` take any value.
+pub fn masks(operations: &[Operation], bits: u32) -> String {
+    let mut rv = String::new();
+    for (idx, op) in operations.iter().enumerate() {
+        if op.op != Op::NewMask {
+            continue;
+        }
+        rv += &format!("line {}: mask\n", idx + 1);
+        rv += &format!("  and      {}\n", binary(op.a, bits));
+        rv += &format!("  or       {}\n", binary(op.b, bits));
+        rv += &format!("  floating {} ({} addresses)\n", binary(op.a, bits), 1u128 << op.a.count_ones());
+    }
+    rv
+}
+
+pub fn memory_q1(memory: &BTreeMap<usize, usize>) -> String {
+    memory.iter()
+        .map(|(addr, v)| format!("mem[{}] = {}\n", addr, v))
+        .collect()
+}
+
+// Floating writes sorted by their lowest address, X for floating bits.
+pub fn memory_q2(memory: &FloatingMemory, bits: u32) -> String {
+    let mut writes = memory.writes().to_vec();
+    writes.sort_by_key(|(p, _)| (p.fixed, p.floating));
+
+    let mut rv = String::new();
+    for (p, v) in writes {
+        let addr: String = (0..bits).rev()
+            .map(|b| {
+                if p.floating & (1 << b) != 0 {
+                    'X'
+                } else if p.fixed & (1 << b) != 0 {
+                    '1'
+                } else {
+                    '0'
+                }
+            })
+            .collect();
+        rv += &format!("mem[{}] = {} ({} addresses)\n", addr, v, p.count());
+    }
+    rv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::DEFAULT_BITS;
+
+    #[test]
+    fn test_masks() {
+        let contents = Operation::read("day_14/test_2.txt", DEFAULT_BITS).unwrap();
+        let rv = masks(&contents, DEFAULT_BITS);
+        let lines: Vec<&str> = rv.lines().collect();
+        assert_eq!(lines.len(), 8);
+        assert_eq!(lines[0], "line 1: mask");
+        assert_eq!(lines[1], "  and      000000000000000000000000000000100001");
+        assert_eq!(lines[2], "  or       000000000000000000000000000000010010");
+        assert_eq!(lines[3], "  floating 000000000000000000000000000000100001 (4 addresses)");
+        assert_eq!(lines[4], "line 3: mask");
+    }
+
+    #[test]
+    fn test_memory_q1_sorted() {
+        let contents = Operation::read("day_14/test_1.txt", DEFAULT_BITS).unwrap();
+        let rv = memory_q1(&Operation::memory_q1(&contents, DEFAULT_BITS));
+        assert_eq!(rv, "mem[7] = 101\nmem[8] = 64\n");
+    }
+
+    #[test]
+    fn test_memory_q2() {
+        let contents = Operation::read("day_14/test_2.txt", DEFAULT_BITS).unwrap();
+        let rv = memory_q2(&FloatingMemory::run(&contents, DEFAULT_BITS), DEFAULT_BITS);
+        // 26, 27, 58 and 59 written first, then 26 and 27 overwritten by the second pattern
+        assert_eq!(rv, "mem[00000000000000000000000000000001X0XX] = 1 (8 addresses)\n\
+                        mem[00000000000000000000000000000011101X] = 100 (2 addresses)\n");
+    }
+}
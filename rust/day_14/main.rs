@@ -2,9 +2,25 @@ use std::{io, env};
 use std::fs::File;
 use std::io::{BufReader, BufRead};
 use crate::Op::NewMask;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-static BITS: usize = 36;
+mod floating;
+mod inspect;
+
+use floating::FloatingMemory;
+
+// Word width of the decoder chip, masks have to be that long.
+const DEFAULT_BITS: u32 = 36;
+const MAX_BITS: u32 = usize::BITS;
+
+// All the bits of a word.
+fn word(bits: u32) -> usize {
+    if bits >= usize::BITS {
+        usize::MAX
+    } else {
+        (1 << bits) - 1
+    }
+}
 
 #[derive(Debug,PartialEq,Eq,Clone)]
 enum Op {
@@ -20,61 +36,78 @@ struct Operation {
 }
 
 impl Operation {
-    fn empty_mask() -> Operation {
+    fn empty_mask(bits: u32) -> Operation {
         Operation {
             op: NewMask,
-            a: (1 << (BITS + 1)) - 1,  // keep all bits
+            a: word(bits),  // keep all bits
             b: 0,
         }
     }
 
-    fn read(path: &str) -> io::Result<Vec<Operation>> {
+    fn read(path: &str, bits: u32) -> io::Result<Vec<Operation>> {
         let file = File::open(path)?;
         let br = BufReader::new(file);
         let mut rv = Vec::new();
 
-        for line in br.lines() {
+        for (idx, line) in br.lines().enumerate() {
             let line = line?;
-            rv.push(Operation::from_string(line));
+            let op = Operation::from_string(line, bits)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", idx + 1, e)))?;
+            rv.push(op);
         }
         Ok(rv)
     }
 
-    fn from_string(line: String) -> Operation {
+    fn from_string(line: String, bits: u32) -> Result<Operation, String> {
         use Op::*;
-        if line.starts_with("mask = ") {
+        if bits == 0 || bits > MAX_BITS {
+            return Err(format!("word width has to be between 1 and {} bits", MAX_BITS));
+        }
+        if let Some(mask) = line.strip_prefix("mask = ") {
             let mut or = 0;
             let mut and = 0;
             // mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X
             // left = most significant
-            let line = line.replace("mask = ", "");
-            for (rbit, c) in line.chars().enumerate() {
-                let v = 1 << (BITS - rbit -1);
-                if c == 'X' {
-                    or += v;
-                } else if c == '1' {
-                    and += v;
+            if mask.len() != bits as usize {
+                return Err(format!("mask has {} bits, expected {}", mask.len(), bits));
+            }
+            for (rbit, c) in mask.chars().enumerate() {
+                let v = 1 << (bits as usize - rbit - 1);
+                match c {
+                    'X' => or += v,
+                    '1' => and += v,
+                    '0' => (),
+                    _ => return Err(format!("unexpected {:?} in mask", c)),
                 }
             }
-            Operation{op: NewMask, a: or, b: and}
+            Ok(Operation{op: NewMask, a: or, b: and})
         } else {
-            let parts: Vec<&str> = line.split(" = ").collect();
             // mem[8] = 11
-            Operation {
+            let (addr, value) = line.strip_prefix("mem[")
+                .and_then(|l| l.split_once("] = "))
+                .ok_or_else(|| format!("expected mask = ... or mem[addr] = value, got {:?}", line))?;
+            let fits = |name: &str, v: &str| -> Result<usize, String> {
+                let v: usize = v.parse().map_err(|e| format!("invalid {} {:?}: {}", name, v, e))?;
+                if v & !word(bits) != 0 {
+                    return Err(format!("{} {} does not fit in {} bits", name, v, bits));
+                }
+                Ok(v)
+            };
+            Ok(Operation {
                 op: Write,
-                a: parts[0].replace("mem[", "").replace("]", "").parse().unwrap(),
-                b: parts[1].parse().unwrap(),
-            }
+                a: fits("address", addr)?,
+                b: fits("value", value)?,
+            })
         }
     }
 
-    fn ignore_mask(&self) -> bool {
+    fn ignore_mask(&self, bits: u32) -> bool {
         use Op::*;
-        self.op == NewMask && self.a == (1 << (BITS + 1)) - 1
+        self.op == NewMask && self.a == word(bits)
     }
 
-    fn apply_on_value(&self, value: usize) -> usize {
-        if !self.ignore_mask() {
+    fn apply_on_value(&self, value: usize, bits: u32) -> usize {
+        if !self.ignore_mask(bits) {
             let v = value & self.a;
             v + self.b
         } else {
@@ -82,70 +115,88 @@ impl Operation {
         }
     }
 
-    fn apply_on_addr(&self, addr: usize) -> Vec<usize> {
+    fn apply_on_addr(&self, addr: usize, bits: u32) -> Vec<usize> {
         let base = (addr & (!self.a)) | self.b;
         let mut rv = HashSet::new();
         rv.insert(0);
-        for b in 0..BITS+1 {
+        for b in 0..bits {
             if self.a & (1 << b) != 0 {
                 for r in rv.clone() {
                     rv.insert(r + (1 << b));
                 }
             }
         }
-        rv.iter().map(|v| *v | base).collect()
+        let mut rv: Vec<usize> = rv.iter().map(|v| *v | base).collect();
+        rv.sort_unstable();
+        rv
     }
 
-    fn execute_q1(operations: &[Operation]) -> usize {
+    fn memory_q1(operations: &[Operation], bits: u32) -> BTreeMap<usize, usize> {
         use Op::*;
-        let mut rv = HashMap::new();
-        let mut last_mask = Operation::empty_mask();
+        let mut rv = BTreeMap::new();
+        let mut last_mask = Operation::empty_mask(bits);
 
         for op in operations {
-            // println!("exec {:?} (m={:?})", op, last_mask);
             if op.op == NewMask {
                 last_mask = op.clone();
             } else {
-                let v = last_mask.apply_on_value(op.b);
+                let v = last_mask.apply_on_value(op.b, bits);
                 rv.insert(op.a, v);
             }
         }
+        rv
+    }
 
-        rv.values().sum()
+    // u128 so 64 bits values do not overflow when summed
+    fn execute_q1(operations: &[Operation], bits: u32) -> u128 {
+        Self::memory_q1(operations, bits).values().map(|&v| v as u128).sum()
     }
 
-    fn execute_q2(operations: &[Operation]) -> usize {
+    fn execute_q2(operations: &[Operation], bits: u32) -> u128 {
         use Op::*;
         let mut rv = HashMap::new();
-        let mut last_mask = Operation::empty_mask();
+        let mut last_mask = Operation::empty_mask(bits);
 
         for op in operations {
-            println!("exec {:?} (m={:?})", op, last_mask);
             if op.op == NewMask {
                 last_mask = op.clone();
             } else {
-                let addrs = last_mask.apply_on_addr(op.a);
-                // println!("  write {} to {} addrs", op.b, addrs.len());
+                let addrs = last_mask.apply_on_addr(op.a, bits);
                 for a in addrs {
                     rv.insert(a, op.b);
                 }
             }
         }
 
-        rv.values().sum()
+        rv.values().map(|&v| v as u128).sum()
     }
 }
 
 fn main() {
-    // Do NOT run test_1, q2 will consume a lot of memory as most masks have a lot of Xs!
+    // Do NOT use --expand with test_1, it will consume a lot of memory as most masks have a lot of Xs!
     let path = env::args().nth(1).expect("please supply a path");
-    let contents = Operation::read(&path).expect("no content");
+    let bits = env::args()
+        .find_map(|a| a.strip_prefix("--bits=").map(|b| b.parse().expect("need a number of bits")))
+        .unwrap_or(DEFAULT_BITS);
+    let contents = Operation::read(&path, bits).expect("no content");
 
     println!("Loaded {} operations", contents.len());
-    let mem_sum = Operation::execute_q1(&contents);
+    if env::args().any(|a| a == "--inspect") {
+        print!("{}", inspect::masks(&contents, bits));
+        println!("Q1 memory:");
+        print!("{}", inspect::memory_q1(&Operation::memory_q1(&contents, bits)));
+        println!("Q2 memory:");
+        print!("{}", inspect::memory_q2(&FloatingMemory::run(&contents, bits), bits));
+    }
+
+    let mem_sum = Operation::execute_q1(&contents, bits);
     println!("Q1: Mem sum {}", mem_sum);
 
-    let mem_sum = Operation::execute_q2(&contents);
+    if env::args().any(|a| a == "--expand") {
+        let mem_sum = Operation::execute_q2(&contents, bits);
+        println!("Q2: Mem sum {} (expanded)", mem_sum);
+    }
+    let mem_sum = FloatingMemory::run(&contents, bits).sum();
     println!("Q2: Mem sum {}", mem_sum);
 }
 
@@ -156,40 +207,79 @@ mod tests {
 
     #[rstest()]
     fn test_empty_mask() {
-        assert!(Operation::empty_mask().ignore_mask());
+        assert!(Operation::empty_mask(DEFAULT_BITS).ignore_mask(DEFAULT_BITS));
+        assert_eq!(Operation::empty_mask(DEFAULT_BITS).a, 0xF_FFFF_FFFF);
+        assert_eq!(Operation::empty_mask(64).a, usize::MAX);
+        assert_eq!(Operation::empty_mask(4).apply_on_value(11, 4), 11);
     }
 
     #[rstest()]
     fn test_from_string() {
-        let op = Operation::from_string("mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X".to_string());
+        let op = Operation::from_string("mask = XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X".to_string(), DEFAULT_BITS).unwrap();
         assert_eq!(op.b, 64);
     }
 
     #[rstest()]
     fn test_apply_on_addr() {
-        let op = Operation::from_string("mask = 000000000000000000000000000000X1001X".to_string());
+        let op = Operation::from_string("mask = 000000000000000000000000000000X1001X".to_string(), DEFAULT_BITS).unwrap();
         assert_eq!(op.b, 18);
         assert_eq!(op.a, 33);
-        assert_eq!(op.apply_on_addr(42), vec!(26, 27, 58, 59))
+        assert_eq!(op.apply_on_addr(42, DEFAULT_BITS), vec!(26, 27, 58, 59))
     }
 
     #[rstest(path, exp_mem,
     case(&"day_14/test_1.txt", 165),
     )]
-    fn test_execute_q1(path: &str, exp_mem: usize) {
-        let contents = Operation::read(&path);
+    fn test_execute_q1(path: &str, exp_mem: u128) {
+        let contents = Operation::read(path, DEFAULT_BITS);
         assert!(contents.is_ok());
         let contents = contents.unwrap();
-        assert_eq!(Operation::execute_q1(&contents), exp_mem);
+        assert_eq!(Operation::execute_q1(&contents, DEFAULT_BITS), exp_mem);
     }
 
     #[rstest(path, exp_mem,
     case(&"day_14/test_2.txt", 208),
     )]
-    fn test_execute_q2(path: &str, exp_mem: usize) {
-        let contents = Operation::read(&path);
+    fn test_execute_q2(path: &str, exp_mem: u128) {
+        let contents = Operation::read(path, DEFAULT_BITS);
         assert!(contents.is_ok());
         let contents = contents.unwrap();
-        assert_eq!(Operation::execute_q2(&contents), exp_mem);
+        assert_eq!(Operation::execute_q2(&contents, DEFAULT_BITS), exp_mem);
+    }
+
+    #[rstest(line, bits, exp_a, exp_b,
+    case("mask = X1X0", 4, 0b1010, 0b0100),
+    case("mem[15] = 15", 4, 15, 15),
+    case("mem[18446744073709551615] = 18446744073709551615", 64, usize::MAX, usize::MAX),
+    )]
+    fn test_from_string_width(line: &str, bits: u32, exp_a: usize, exp_b: usize) {
+        let op = Operation::from_string(line.to_string(), bits).unwrap();
+        assert_eq!((op.a, op.b), (exp_a, exp_b));
+    }
+
+    #[rstest(line, bits,
+    case("mask = X1X0", 36),
+    case("mask = X1X0", 3),
+    case("mask = X1Y0", 4),
+    case("mem[16] = 1", 4),
+    case("mem[1] = 16", 4),
+    case("mem[1] = 18446744073709551616", 64),
+    case("mem[1] = 1", 0),
+    case("mem[1] = 1", 65),
+    case("mem[1] 1", 36),
+    case("", 36),
+    )]
+    fn test_from_string_invalid(line: &str, bits: u32) {
+        assert!(Operation::from_string(line.to_string(), bits).is_err());
+    }
+
+    #[test]
+    fn test_execute_64_bits() {
+        let ops: Vec<Operation> = [
+            format!("mask = {}", "X".repeat(64)),
+            format!("mem[0] = {}", usize::MAX),
+            format!("mem[1] = {}", usize::MAX),
+        ].iter().map(|l| Operation::from_string(l.to_string(), 64).unwrap()).collect();
+        assert_eq!(Operation::execute_q1(&ops, 64), 2 * usize::MAX as u128);
     }
 }
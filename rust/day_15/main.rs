@@ -1,81 +1,124 @@
 use std::{io, env};
 use std::fs::File;
 use std::io::{BufReader, BufRead};
-use std::collections::HashMap;
+use std::time::Instant;
 
-fn read(path: &str) -> io::Result<Vec<usize>> {
+fn read(path: &str) -> io::Result<Vec<u32>> {
     let file = File::open(path)?;
     let br = BufReader::new(file);
     let mut rv = Vec::new();
 
     for line in br.lines() {
         let line = line?;
-        for c in line.split(",") {
-            let v: usize = c.parse().unwrap();
+        for c in line.split(',') {
+            let v = c.trim().parse()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}: {}", c, e)))?;
             rv.push(v);
         }
     }
     Ok(rv)
 }
 
-fn memory_game(first_numbers: &[usize], turns: usize) -> usize {
-    let mut memory: HashMap<usize, Vec<usize>> = HashMap::new();
-    let mut t = 1;
-
-    // assumes turns > first_numbers.len()
-    // we don't store the last number yet
-    let mut last_n = 0;
-    for n in first_numbers {
-        memory.insert(*n, vec!(t));
-        // println!("t={} start {}", t, n);
-        t += 1;
-        last_n = *n;
+// Van Eck sequence: after the seed, each number is how many turns ago the
+// previous number was last spoken, or 0 when it was new.
+struct MemoryGame {
+    seed: Vec<u32>,
+    // turn (from 1) each number was last spoken at, 0 for never
+    // the previous number is only stored once the next one is known
+    last_seen: Vec<u32>,
+    turn: u32,
+    last: u32,
+}
+
+impl MemoryGame {
+    fn new(seed: &[u32]) -> MemoryGame {
+        Self::with_capacity(seed, 0)
     }
 
-    while t <= turns {
-        let next_n = if memory.contains_key(&last_n) {
-            let info = &memory[&last_n];
-            if info.len() > 1 {
-                // println!("t={} {} was last said at turns {:?}", t, last_n, info);
-                info[info.len() - 1] - info[info.len() - 2]
-            } else {
-                // println!("t={} {} was new!", t, last_n);
-                0 as usize
-            }
+    // Spoken numbers are smaller than the number of turns,
+    // reserving that many avoids growing the memory while playing.
+    fn with_capacity(seed: &[u32], turns: usize) -> MemoryGame {
+        let largest = seed.iter().max().map_or(0, |&v| v as usize + 1);
+        MemoryGame {
+            seed: seed.to_vec(),
+            last_seen: vec![0; largest.max(turns)],
+            turn: 0,
+            last: 0,
+        }
+    }
+
+    fn seen_at(&self, n: u32) -> u32 {
+        self.last_seen.get(n as usize).cloned().unwrap_or(0)
+    }
+}
+
+impl Iterator for MemoryGame {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.turn == u32::MAX {
+            return None;
+        }
+        let t = self.turn + 1;
+        let spoken = if (t as usize) <= self.seed.len() {
+            self.seed[t as usize - 1]
         } else {
-            // println!("t={} {} was super new!", t, last_n);
-            0 as usize
+            match self.seen_at(self.last) {
+                0 => 0,
+                seen => self.turn - seen,
+            }
         };
-        let info = memory.get(&next_n);
-        if let Some(info) = info {
-            // keep only the last 2
-            let new_info = vec!(*info.last().unwrap(), t);
-            memory.insert(next_n, new_info);
-        } else {
-            memory.insert(next_n, vec!(t));
+        if t > 1 {
+            let prev = self.last as usize;
+            if prev >= self.last_seen.len() {
+                let len = (prev + 1).max(2 * self.last_seen.len());
+                self.last_seen.resize(len, 0);
+            }
+            self.last_seen[prev] = self.turn;
         }
-        t += 1;
-        last_n = next_n;
+        self.turn = t;
+        self.last = spoken;
+        Some(spoken)
     }
+}
 
-    last_n
+// Number spoken at the given turn (from 1), None for turn 0 or past u32::MAX.
+fn memory_game(first_numbers: &[u32], turns: usize) -> Option<u32> {
+    let idx = turns.checked_sub(1)?;
+    MemoryGame::with_capacity(first_numbers, turns).nth(idx)
 }
 
 fn main() {
     let path = env::args().nth(1).expect("please supply a path");
     let contents = read(&path).expect("no content");
 
-    let last_spoken = memory_game(&contents, 2020);
+    let last_spoken = memory_game(&contents, 2020).expect("2020 is a valid turn");
     println!("Last spoken after 2020 turns: {}", last_spoken);
 
-    let last_spoken = memory_game(&contents, 30000000);
+    let start = env::args().any(|a| a == "--bench").then(Instant::now);
+    let last_spoken = memory_game(&contents, 30000000).expect("30,000,000 is a valid turn");
     println!("Last spoken after 30,000,000 turns: {}", last_spoken);
+    if let Some(start) = start {
+        println!("30,000,000 turns in {:?}", start.elapsed());
+    }
+
+    // --find=v,w: first turn each value is spoken, within the first 30,000,000 turns
+    if let Some(values) = env::args().find_map(|a| a.strip_prefix("--find=").map(|v| v.to_string())) {
+        for v in values.split(',') {
+            let v: u32 = v.parse().expect("need a number to find");
+            match MemoryGame::new(&contents).take(30000000).position(|n| n == v) {
+                Some(idx) => println!("{} is first spoken at turn {}", v, idx + 1),
+                None => println!("{} is not spoken in 30,000,000 turns", v),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use rstest::rstest;
+    use std::collections::HashSet;
 
     #[rstest(input, turns, output,
     case(vec!(0, 3, 6), 4, 0),
@@ -88,17 +131,62 @@ mod tests {
     case(vec!(3, 2, 1), 2020, 438),
     case(vec!(3, 1, 2), 2020, 1836),
     )]
-    fn test_memory_game(input: Vec<usize>, turns: usize, output: usize) {
+    fn test_memory_game(input: Vec<u32>, turns: usize, output: u32) {
         println!("Running for {} turns", turns);
-        assert_eq!(memory_game(&input, turns), output);
+        assert_eq!(memory_game(&input, turns), Some(output));
     }
 
     #[rstest(input, turns, output,
     case(vec!(0, 3, 6), 30000000, 175594),
     case(vec!(1, 3, 2), 30000000, 2578),
     )]
-    fn test_slow_memory_game(input: Vec<usize>, turns: usize, output: usize) {
+    fn test_slow_memory_game(input: Vec<u32>, turns: usize, output: u32) {
         println!("Running for {} turns", turns);
-        assert_eq!(memory_game(&input, turns), output);
+        assert_eq!(memory_game(&input, turns), Some(output));
+    }
+
+    // cargo test --release --bin day_15 -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bench_memory_game() {
+        let runs = 3;
+        let best = (0..runs).map(|_| {
+            let start = Instant::now();
+            assert_eq!(memory_game(&[0, 3, 6], 30000000), Some(175594));
+            start.elapsed()
+        }).min().unwrap();
+        println!("30,000,000 turns: best of {} runs in {:?}", runs, best);
+    }
+
+    #[test]
+    fn test_no_turn() {
+        assert_eq!(memory_game(&[0, 3, 6], 0), None);
+        assert_eq!(memory_game(&[0, 3, 6], 1), Some(0));
+    }
+
+    #[test]
+    fn test_sequence() {
+        let rv: Vec<u32> = MemoryGame::new(&[0, 3, 6]).take(10).collect();
+        assert_eq!(rv, vec![0, 3, 6, 0, 3, 3, 1, 0, 4, 0]);
+    }
+
+    #[test]
+    fn test_sequence_from_any_seed() {
+        // the plain Van Eck sequence starts from a single 0
+        let rv: Vec<u32> = MemoryGame::new(&[0]).take(12).collect();
+        assert_eq!(rv, vec![0, 0, 1, 0, 2, 0, 2, 2, 1, 6, 0, 5]);
+        // numbers larger than anything seen so far
+        let rv: Vec<u32> = MemoryGame::new(&[1000, 1000]).take(5).collect();
+        assert_eq!(rv, vec![1000, 1000, 1, 0, 0]);
+        assert_eq!(MemoryGame::new(&[]).next(), Some(0));
+    }
+
+    #[test]
+    fn test_search_and_statistics() {
+        let game = || MemoryGame::new(&[0, 3, 6]);
+        assert_eq!(game().position(|n| n == 436).map(|i| i + 1), Some(2020));
+        assert_eq!(game().take(2020).max(), Some(1670));
+        let distinct: HashSet<u32> = game().take(2020).collect();
+        assert_eq!(distinct.len(), 382);
     }
 }
@@ -0,0 +1,259 @@
+use std::collections::BTreeSet;
+use super::Rule;
+
+// Stop looking for more assignments after that many.
+const MAX_SOLUTIONS: usize = 100;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Assignment {
+    // field of each column
+    Unique(Vec<String>),
+    // the columns which get a different field depending on the solution,
+    // with every field they can take; truncated when the search stopped
+    // at MAX_SOLUTIONS and there are more solutions than listed
+    Ambiguous {
+        solutions: Vec<Vec<String>>,
+        columns: Vec<(usize, Vec<String>)>,
+        truncated: bool,
+    },
+    // columns no field fits, and fields which fit no column;
+    // both empty when several columns compete for too few fields
+    Impossible {
+        columns: Vec<usize>,
+        fields: Vec<String>,
+    },
+}
+
+// Can column i hold rule j: does the rule accept column i of every ticket?
+pub struct Compatibility {
+    matrix: Vec<Vec<bool>>,
+}
+
+impl Compatibility {
+    pub fn new(rules: &[Rule], tickets: &[Vec<usize>]) -> Compatibility {
+        let matrix = (0..rules.len())
+            .map(|col| rules.iter()
+                .map(|r| tickets.iter().all(|t| t.get(col).is_some_and(|&v| r.is_valid(v))))
+                .collect())
+            .collect();
+        Compatibility { matrix }
+    }
+
+    fn candidates(&self, col: usize, used: &[bool]) -> Vec<usize> {
+        (0..self.matrix[col].len())
+            .filter(|&rule| self.matrix[col][rule] && !used[rule])
+            .collect()
+    }
+
+    // Try to give col a rule, taking it from another column which can move
+    // to a different rule if needed (augmenting path).
+    fn augment(&self, col: usize, used: &[bool], owner: &mut Vec<Option<usize>>, seen: &mut Vec<bool>) -> bool {
+        for rule in 0..used.len() {
+            if !self.matrix[col][rule] || used[rule] || seen[rule] {
+                continue;
+            }
+            seen[rule] = true;
+            let free = match owner[rule] {
+                Some(other) => self.augment(other, used, owner, seen),
+                None => true,
+            };
+            if free {
+                owner[rule] = Some(col);
+                return true;
+            }
+        }
+        false
+    }
+
+    // Can every column not assigned yet still get its own unused rule?
+    fn has_matching(&self, assigned: &[Option<usize>], used: &[bool]) -> bool {
+        let mut owner = vec![None; used.len()];
+        (0..assigned.len())
+            .filter(|&col| assigned[col].is_none())
+            .all(|col| self.augment(col, used, &mut owner, &mut vec![false; used.len()]))
+    }
+
+    // Backtracking, always on the column with the fewest candidates left.
+    // Branches without a complete matching are cut, so every branch searched
+    // leads to a solution and impossible inputs stop right away.
+    fn search(&self, assigned: &mut Vec<Option<usize>>, used: &mut Vec<bool>, rv: &mut Vec<Vec<usize>>, limit: usize) {
+        if rv.len() >= limit || !self.has_matching(assigned, used) {
+            return;
+        }
+        let next = (0..assigned.len())
+            .filter(|&col| assigned[col].is_none())
+            .map(|col| (col, self.candidates(col, used)))
+            .min_by_key(|(_, c)| c.len());
+        let (col, candidates) = match next {
+            Some(n) => n,
+            None => {
+                rv.push(assigned.iter().map(|a| a.unwrap()).collect());
+                return;
+            }
+        };
+        for rule in candidates {
+            assigned[col] = Some(rule);
+            used[rule] = true;
+            self.search(assigned, used, rv, limit);
+            used[rule] = false;
+            assigned[col] = None;
+        }
+    }
+
+    // Every way to give each column its own rule, up to `limit`.
+    pub fn solve(&self, limit: usize) -> Vec<Vec<usize>> {
+        let mut rv = Vec::new();
+        let mut assigned = vec![None; self.matrix.len()];
+        let mut used = vec![false; self.matrix.first().map_or(0, |r| r.len())];
+        self.search(&mut assigned, &mut used, &mut rv, limit);
+        rv
+    }
+
+    pub fn assign(&self, rules: &[Rule]) -> Assignment {
+        let name = |rule: usize| rules[rule].field.clone();
+        // one more than kept, to tell whether the search was cut short
+        let mut solutions = self.solve(MAX_SOLUTIONS + 1);
+        let truncated = solutions.len() > MAX_SOLUTIONS;
+        solutions.truncate(MAX_SOLUTIONS);
+
+        match solutions.len() {
+            0 => Assignment::Impossible {
+                columns: (0..self.matrix.len())
+                    .filter(|&col| !self.matrix[col].contains(&true))
+                    .collect(),
+                fields: (0..rules.len())
+                    .filter(|&rule| !self.matrix.iter().any(|row| row[rule]))
+                    .map(name)
+                    .collect(),
+            },
+            1 => Assignment::Unique(solutions[0].iter().map(|&r| name(r)).collect()),
+            _ => {
+                let columns = (0..self.matrix.len())
+                    .filter_map(|col| {
+                        let fields: BTreeSet<usize> = solutions.iter().map(|s| s[col]).collect();
+                        if fields.len() > 1 {
+                            Some((col, fields.into_iter().map(name).collect()))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect();
+                Assignment::Ambiguous {
+                    solutions: solutions.iter().map(|s| s.iter().map(|&r| name(r)).collect()).collect(),
+                    columns,
+                    truncated,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn rules(names: &[&str]) -> Vec<Rule> {
+        names.iter().map(|n| Rule { field: n.to_string(), ranges: Vec::new() }).collect()
+    }
+
+    fn compatibility(matrix: &[&str]) -> Compatibility {
+        Compatibility {
+            matrix: matrix.iter().map(|row| row.chars().map(|c| c == '1').collect()).collect(),
+        }
+    }
+
+    #[rstest(matrix, exp,
+    // no column has a single candidate to start from
+    case(&["110", "111", "011"], 3),
+    case(&["100", "110", "111"], 1),
+    case(&["11", "11"], 2),
+    case(&["111", "111", "111"], 6),
+    case(&["10", "10"], 0),
+    case(&[], 1),
+    )]
+    fn test_solve(matrix: &[&str], exp: usize) {
+        assert_eq!(compatibility(matrix).solve(MAX_SOLUTIONS).len(), exp);
+    }
+
+    #[test]
+    fn test_solve_limit() {
+        let matrix = vec!["11111111"; 8];
+        assert_eq!(compatibility(&matrix).solve(MAX_SOLUTIONS).len(), MAX_SOLUTIONS);
+    }
+
+    // too many columns competing for the same rules, which used to take factorial time
+    #[rstest(matrix, exp_fields,
+    case(vec![format!("{}0", "1".repeat(19)); 20], vec!["19"]),
+    case([vec!["1".repeat(20)], vec![format!("{}00", "1".repeat(18)); 19]].concat(), vec![]),
+    )]
+    fn test_assign_impossible_large(matrix: Vec<String>, exp_fields: Vec<&str>) {
+        let names: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+        let rules = rules(&names.iter().map(|n| n.as_str()).collect::<Vec<_>>());
+        let matrix: Vec<&str> = matrix.iter().map(|r| r.as_str()).collect();
+        assert_eq!(compatibility(&matrix).assign(&rules), Assignment::Impossible {
+            columns: vec![],
+            fields: exp_fields.iter().map(|f| f.to_string()).collect(),
+        });
+    }
+
+    #[test]
+    fn test_assign_unique() {
+        let rules = rules(&["a", "b", "c"]);
+        assert_eq!(
+            compatibility(&["001", "011", "111"]).assign(&rules),
+            Assignment::Unique(vec!["c".to_string(), "b".to_string(), "a".to_string()]),
+        );
+    }
+
+    #[test]
+    fn test_assign_ambiguous() {
+        let rules = rules(&["a", "b", "c"]);
+        let rv = compatibility(&["110", "110", "111"]).assign(&rules);
+        let columns = match rv {
+            Assignment::Ambiguous { solutions, columns, truncated } => {
+                assert_eq!(solutions.len(), 2);
+                assert!(!truncated);
+                assert!(solutions.iter().all(|s| s[2] == "c"));
+                columns
+            }
+            other => panic!("expected ambiguous, got {:?}", other),
+        };
+        let ab = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(columns, vec![(0, ab.clone()), (1, ab)]);
+    }
+
+    #[rstest(size, exp_truncated,
+    // 5! = 120 solutions, more than are kept
+    case(5, true),
+    // 4! = 24 solutions, all of them kept
+    case(4, false),
+    )]
+    fn test_assign_truncated(size: usize, exp_truncated: bool) {
+        let names: Vec<String> = (0..size).map(|i| i.to_string()).collect();
+        let rules = rules(&names.iter().map(|n| n.as_str()).collect::<Vec<_>>());
+        let matrix = vec!["1".repeat(size); size];
+        let matrix: Vec<&str> = matrix.iter().map(|r| r.as_str()).collect();
+        match compatibility(&matrix).assign(&rules) {
+            Assignment::Ambiguous { solutions, truncated, .. } => {
+                assert_eq!(truncated, exp_truncated);
+                assert_eq!(solutions.len(), if exp_truncated { MAX_SOLUTIONS } else { 24 });
+            }
+            other => panic!("expected ambiguous, got {:?}", other),
+        }
+    }
+
+    #[rstest(matrix, exp_columns, exp_fields,
+    case(&["100", "000", "011"], vec![1], vec![]),
+    case(&["100", "100", "001"], vec![], vec!["b"]),
+    case(&["110", "110", "110"], vec![], vec!["c"]),
+    case(&["101", "101", "101"], vec![], vec!["b"]),
+    )]
+    fn test_assign_impossible(matrix: &[&str], exp_columns: Vec<usize>, exp_fields: Vec<&str>) {
+        let rules = rules(&["a", "b", "c"]);
+        assert_eq!(compatibility(matrix).assign(&rules), Assignment::Impossible {
+            columns: exp_columns,
+            fields: exp_fields.iter().map(|f| f.to_string()).collect(),
+        });
+    }
+}
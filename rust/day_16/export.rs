@@ -0,0 +1,66 @@
+use std::io;
+use std::io::Write;
+
+use super::Rule;
+
+// One row per ticket, the columns named after their field.
+pub fn write_tickets<W: Write>(columns: &[String], tickets: &[Vec<usize>], writer: W) -> io::Result<()> {
+    let mut wr = csv::Writer::from_writer(writer);
+    wr.write_record(columns)?;
+    for t in tickets {
+        wr.write_record(t.iter().map(|v| v.to_string()))?;
+    }
+    wr.flush()
+}
+
+// Position of each invalid nearby ticket (from 1), its error rate
+// and the values which fit no rule.
+pub fn write_errors<W: Write>(invalid: &[(usize, &[usize], usize)], rules: &[Rule], writer: W) -> io::Result<()> {
+    let mut wr = csv::Writer::from_writer(writer);
+    wr.write_record(["ticket", "error_rate", "invalid_values"])?;
+    for (idx, ticket, rate) in invalid {
+        let values: Vec<String> = ticket.iter()
+            .filter(|&&v| !rules.iter().any(|r| r.is_valid(v)))
+            .map(|v| v.to_string())
+            .collect();
+        wr.write_record(&[(idx + 1).to_string(), rate.to_string(), values.join(" ")])?;
+    }
+    wr.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::Input;
+
+    #[test]
+    fn test_write_tickets() {
+        let mut contents = Input::read("day_16/test_1.txt").unwrap();
+        contents.discard_invalid_tickets();
+        let columns: Vec<String> = ["row", "class", "seat"].iter().map(|c| c.to_string()).collect();
+
+        let mut rv = Vec::new();
+        write_tickets(&columns, &contents.nearby_tickets, &mut rv).unwrap();
+        assert_eq!(String::from_utf8(rv).unwrap(), "row,class,seat\n7,3,47\n");
+    }
+
+    #[test]
+    fn test_write_errors() {
+        let contents = Input::read("day_16/test_1.txt").unwrap();
+        let mut rv = Vec::new();
+        write_errors(&contents.invalid_tickets(), &contents.rules, &mut rv).unwrap();
+        assert_eq!(
+            String::from_utf8(rv).unwrap(),
+            "ticket,error_rate,invalid_values\n2,4,4\n3,55,55\n4,12,12\n",
+        );
+    }
+
+    #[test]
+    fn test_write_errors_zero() {
+        // a 0 which fits no rule: invalid ticket with an error rate of 0
+        let contents = Input::from_string("a: 1-3\n\nyour ticket:\n1\n\nnearby tickets:\n0\n2\n").unwrap();
+        let mut rv = Vec::new();
+        write_errors(&contents.invalid_tickets(), &contents.rules, &mut rv).unwrap();
+        assert_eq!(String::from_utf8(rv).unwrap(), "ticket,error_rate,invalid_values\n1,0,0\n");
+    }
+}
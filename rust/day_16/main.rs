@@ -1,35 +1,57 @@
-use std::{io, env};
+use std::{io, env, fs};
 use std::fs::File;
-use std::io::{BufReader, BufRead};
-use std::collections::{HashMap, HashSet};
+
+mod assign;
+mod export;
+
+use assign::{Assignment, Compatibility};
 
 struct Rule {
     field: String,
     ranges: Vec<(usize, usize)>,
 }
 
+fn invalid(line_no: usize, msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line_no, msg))
+}
+
+fn expect_line<'a>(lines: &mut impl Iterator<Item = (usize, &'a str)>, exp: &str) -> io::Result<()> {
+    match lines.next() {
+        Some((_, l)) if l == exp => Ok(()),
+        Some((i, l)) => Err(invalid(i, format!("expected {:?}, got {:?}", exp, l))),
+        None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("expected {:?}", exp))),
+    }
+}
+
+fn parse_number(value: &str) -> Result<usize, String> {
+    value.parse().map_err(|e| format!("invalid number {:?}: {}", value, e))
+}
+
 impl Rule {
-    fn from_string(value: &str) -> Rule {
-        // println!("Rule from_string: '{}'", value);
-        let entries: Vec<&str> = value.split(":").collect();
-        let field = entries[0];
-        let entries = entries[1].replace(" ", "");
-        let entries: Vec<&str> = entries.split("or").collect();
-
-        let mut ranges = Vec::new();
-        for values in entries {
-            let values: Vec<&str> = values.split("-").collect();
-            let v: (usize, usize) = (
-                values[0].parse().unwrap(),
-                values[1].parse().unwrap(),
-            );
-            ranges.push(v);
+    // departure location: 49-627 or 650-970
+    // the field name can contain anything but ": "
+    fn from_string(value: &str) -> Result<Rule, String> {
+        let (field, ranges) = value.split_once(": ")
+            .ok_or_else(|| format!("expected <field>: <ranges>, got {:?}", value))?;
+        if field.is_empty() {
+            return Err("empty field name".to_string());
         }
 
-        Rule {
-            field: field.to_string(),
-            ranges
+        let mut rv = Vec::new();
+        for range in ranges.split(" or ") {
+            let (low, high) = range.split_once('-')
+                .ok_or_else(|| format!("expected <low>-<high>, got {:?}", range))?;
+            let (low, high) = (parse_number(low)?, parse_number(high)?);
+            if low > high {
+                return Err(format!("empty range {}", range));
+            }
+            rv.push((low, high));
         }
+
+        Ok(Rule {
+            field: field.to_string(),
+            ranges: rv,
+        })
     }
 
     fn is_valid(&self, value: usize) -> bool {
@@ -50,41 +72,54 @@ struct Input {
 
 impl Input {
     fn read(path: &str) -> io::Result<Input> {
-        let file = File::open(path)?;
-        let br = BufReader::new(file);
-        let mut read_rules = true;
-        let mut read_nearby_tickets = false;
-
-        let mut rules = Vec::new();
-        let mut my_ticket = Vec::new();
-        let mut nearby_tickets = Vec::new();
+        Self::from_string(&fs::read_to_string(path)?)
+    }
 
-        for line in br.lines() {
-            let line = line?;
+    // Rules, an empty line, "your ticket:" and its values, an empty line,
+    // "nearby tickets:" then one ticket per line.
+    fn from_string(content: &str) -> io::Result<Input> {
+        let mut lines = content.lines().enumerate().map(|(i, l)| (i + 1, l)).peekable();
 
-            if line.is_empty() {
-                continue;
-            } else if line.starts_with("your ticket") {
-                read_rules = false;
-                continue;
-            } else if line.starts_with("nearby tickets") {
-                read_nearby_tickets = true;
-                continue;
+        let mut rules: Vec<Rule> = Vec::new();
+        while let Some((i, line)) = lines.next_if(|(_, l)| !l.is_empty()) {
+            let rule = Rule::from_string(line).map_err(|e| invalid(i, e))?;
+            if rules.iter().any(|r| r.field == rule.field) {
+                return Err(invalid(i, format!("field {} is defined twice", rule.field)));
             }
-            // now read what's appropriate
-
-            if read_rules {
-                rules.push(Rule::from_string(&line));
-            } else {
-                let ticket = line.split(",").map(|v| v.parse().unwrap()).collect();
-                if read_nearby_tickets {
-                    nearby_tickets.push(ticket);
-                } else {
-                    my_ticket = ticket;
-                }
+            rules.push(rule);
+        }
+        if rules.is_empty() {
+            return Err(invalid(1, "no rule".to_string()));
+        }
+        let ticket = |(i, line): (usize, &str)| -> io::Result<Vec<usize>> {
+            let values = line.split(',')
+                .map(parse_number)
+                .collect::<Result<Vec<usize>, String>>()
+                .map_err(|e| invalid(i, e))?;
+            if values.len() != rules.len() {
+                return Err(invalid(i, format!("{} values for {} fields", values.len(), rules.len())));
             }
-
+            Ok(values)
+        };
+
+        expect_line(&mut lines, "")?;
+        expect_line(&mut lines, "your ticket:")?;
+        let my_ticket = ticket(lines.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "expected your ticket")
+        })?)?;
+        expect_line(&mut lines, "")?;
+        expect_line(&mut lines, "nearby tickets:")?;
+        let mut nearby_tickets = Vec::new();
+        for (i, line) in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+            nearby_tickets.push(ticket((i, line))?);
         }
+        if let Some((i, l)) = lines.find(|(_, l)| !l.is_empty()) {
+            return Err(invalid(i, format!("unexpected {:?} after the nearby tickets", l)));
+        }
+
         Ok(Input {
             rules,
             my_ticket,
@@ -92,6 +127,11 @@ impl Input {
         })
     }
 
+    // A 0 which fits no rule makes the ticket invalid without adding to the error rate.
+    fn is_valid_ticket(&self, ticket: &[usize]) -> bool {
+        ticket.iter().all(|&v| self.rules.iter().any(|r| r.is_valid(v)))
+    }
+
     fn ticket_err_rate(&self, ticket: &[usize]) -> usize {
         let mut err_rate = 0;
         for v in ticket {
@@ -109,16 +149,25 @@ impl Input {
         err_rate
     }
 
+    // (position in the nearby tickets, ticket, error rate)
+    fn invalid_tickets(&self) -> Vec<(usize, &[usize], usize)> {
+        self.nearby_tickets.iter()
+            .enumerate()
+            .filter(|(_, t)| !self.is_valid_ticket(t))
+            .map(|(i, t)| (i, t.as_slice(), self.ticket_err_rate(t)))
+            .collect()
+    }
+
     fn compute_err_rate(&self) -> usize {
         self.nearby_tickets.iter().map(|t| self.ticket_err_rate(t)).sum()
     }
 
     fn discard_invalid_tickets(&mut self) {
         let mut new_tickets = Vec::new();
-        let mut invalid_tickets = 0 as usize;
+        let mut invalid_tickets = 0;
 
         for t in self.nearby_tickets.clone() {
-            if self.ticket_err_rate(&t) == 0 {
+            if self.is_valid_ticket(&t) {
                 new_tickets.push(t);
             } else {
                 invalid_tickets += 1;
@@ -129,77 +178,12 @@ impl Input {
         self.nearby_tickets = new_tickets;
     }
 
-    fn collapse(guesses: &[HashMap<String, usize>]) -> Vec<String> {
-        let mut new_choices:Vec<Vec<String>> = Vec::new();
-        // remove impossible solutions
-        for g in guesses {
-            let max_v = g.values().max().unwrap();
-            let new_g: Vec<String> = g.iter()
-                .filter(|&(_, v)| v == max_v)
-                .map(|(k, _)| k.clone())
-                .collect();
-            new_choices.push(new_g);
-        }
-        let mut collapsed = HashSet::new();
-
-        while collapsed.len() < new_choices.len() {
-            // choose a value to collapse
-            let mut collapse = "".to_string();
-            for v in &new_choices {
-                if v.len() == 1 {
-                    let v = v.first().unwrap();
-                    if !collapsed.contains(v) {
-                        collapsed.insert(v.clone());
-                        collapse = v.clone();
-                        break;
-                    }
-                }
-            }
-            // iterate new_choices and remove all ref to v
-            for v in new_choices.iter_mut() {
-                if v.len() > 1 {
-                    let to_rem: Vec<usize> = v.iter().enumerate()
-                        .filter(|&(_, e)| *e == collapse)
-                        .map(|(i, _)| i)
-                        .collect();
-                    for e in to_rem {
-                        v.remove(e);
-                    }
-                }
-            }
-        }
-
-        new_choices.iter().map(|v|v.first().unwrap().clone()).collect()
-    }
-
-    fn guess_fields(&self) -> Vec<String> {
-        let mut guesses :Vec<HashMap<String, usize>> = Vec::new();
-
-        for t in &self.nearby_tickets {
-            // println!("considering ticket {:?}", t);
-            for (i, v) in t.iter().enumerate() {
-                for r in &self.rules {
-                    if r.is_valid(*v) {
-                        // println!("guesses.len()={} i={}", guesses.len(), i);
-                        while guesses.len() <= i {
-                            guesses.push(HashMap::new());
-                        }
-                        if let Some(guess) = guesses.get_mut(i) {
-                            let v = guess.get(&r.field).unwrap_or(&0);
-                            guess.insert(r.field.clone(), v+1);
-                        } else {
-                            eprintln!("thought it could not happen!");
-                        }
-                    }
-                }
-            }
-            // println!("Guesses are now: {:?}", guesses);
-        }
-        Self::collapse(&guesses)
+    fn assign_fields(&self) -> Assignment {
+        Compatibility::new(&self.rules, &self.nearby_tickets).assign(&self.rules)
     }
 
     fn check_my_ticket(&self, columns: &[String], contains: &str) -> usize {
-        let mut rv = 1 as usize;
+        let mut rv: usize = 1;
         println!("Checking my ticket for {}", contains);
         for (i, c) in columns.iter().enumerate() {
             if c.starts_with(contains) {
@@ -215,17 +199,48 @@ impl Input {
 fn main() {
     let path = env::args().nth(1).expect("please supply a path");
     let mut contents = Input::read(&path).expect("no content");
+    let option = |name: &str| env::args()
+        .find_map(|a| a.strip_prefix(&format!("--{}=", name)).map(|v| v.to_string()));
 
     let err_rate = contents.compute_err_rate();
     println!("Q1: err rate: {}", err_rate);
 
-    contents.discard_invalid_tickets();
+    if let Some(errors_path) = option("errors") {
+        let file = File::create(&errors_path).expect("cannot create error report");
+        export::write_errors(&contents.invalid_tickets(), &contents.rules, file).expect("cannot write error report");
+    }
 
-    let guesses = contents.guess_fields();
-    // println!("Cols: {:?}", guesses);
+    contents.discard_invalid_tickets();
 
-    let rv = contents.check_my_ticket(&guesses, &"departure");
-    println!("Q2: {}", rv);
+    match contents.assign_fields() {
+        Assignment::Unique(columns) => {
+            let rv = contents.check_my_ticket(&columns, "departure");
+            println!("Q2: {}", rv);
+            if let Some(csv_path) = option("csv") {
+                let file = File::create(&csv_path).expect("cannot create csv");
+                export::write_tickets(&columns, &contents.nearby_tickets, file).expect("cannot write csv");
+            }
+        }
+        Assignment::Ambiguous { solutions, columns, truncated } => {
+            if truncated {
+                println!("Q2: at least {} possible assignments (search stopped there)", solutions.len());
+            } else {
+                println!("Q2: {} possible assignments", solutions.len());
+            }
+            for (col, fields) in columns {
+                println!("  column {} can be any of {}", col, fields.join(", "));
+            }
+        }
+        Assignment::Impossible { columns, fields } => {
+            println!("Q2: no possible assignment");
+            for col in columns {
+                println!("  no field fits column {}", col);
+            }
+            for field in fields {
+                println!("  {} fits no column", field);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -233,12 +248,71 @@ mod tests {
     use super::*;
     use rstest::rstest;
 
+    #[rstest(line, exp_field, exp_ranges,
+    case("class: 1-3 or 5-7", "class", vec![(1, 3), (5, 7)]),
+    case("door: 1-3 or 5-7", "door", vec![(1, 3), (5, 7)]),
+    case("floor or door: 10-20", "floor or door", vec![(10, 20)]),
+    case("x: 1-1 or 2-3 or 4-5", "x", vec![(1, 1), (2, 3), (4, 5)]),
+    )]
+    fn test_rule_from_string(line: &str, exp_field: &str, exp_ranges: Vec<(usize, usize)>) {
+        let rule = Rule::from_string(line).unwrap();
+        assert_eq!(rule.field, exp_field);
+        assert_eq!(rule.ranges, exp_ranges);
+    }
+
+    #[rstest(line,
+    case("class 1-3 or 5-7"),
+    case(": 1-3"),
+    case("class: 1-3 5-7"),
+    case("class: 1-3 or"),
+    case("class: 1-a"),
+    case("class: 3-1"),
+    case("class: 1-3or5-7"),
+    )]
+    fn test_rule_from_string_invalid(line: &str) {
+        assert!(Rule::from_string(line).is_err());
+    }
+
+    static VALID: &str = "a: 1-3\nb or c: 5-7\n\nyour ticket:\n1,5\n\nnearby tickets:\n1,6\n2,7\n";
+
+    #[test]
+    fn test_from_string() {
+        let contents = Input::from_string(VALID).unwrap();
+        assert_eq!(contents.rules.len(), 2);
+        assert_eq!(contents.rules[1].field, "b or c");
+        assert_eq!(contents.my_ticket, vec![1, 5]);
+        assert_eq!(contents.nearby_tickets, vec![vec![1, 6], vec![2, 7]]);
+    }
+
+    #[rstest(from, to, exp_line,
+    // missing separator
+    case("\n\nyour", "\nyour", 3),
+    case("your ticket:", "my ticket:", 4),
+    case("1,5\n", "1,x\n", 5),
+    case("1,5\n", "1,5,6\n", 5),
+    case("2,7\n", "2,-7\n", 9),
+    case("a: 1-3", "a: 1-3\na: 4-5", 2),
+    case("2,7\n", "2,7\n\n3,4\n", 11),
+    )]
+    fn test_from_string_invalid(from: &str, to: &str, exp_line: usize) {
+        let input = VALID.replacen(from, to, 1);
+        let err = Input::from_string(&input).err().expect("invalid input");
+        assert!(err.to_string().starts_with(&format!("line {}:", exp_line)), "{}", err);
+    }
+
+    #[test]
+    fn test_from_string_truncated() {
+        assert!(Input::from_string("a: 1-3\n\nyour ticket:\n").is_err());
+        assert!(Input::from_string("a: 1-3\n").is_err());
+        assert!(Input::from_string("").is_err());
+    }
+
     #[rstest(path, exp_err_rate,
     case(&"day_16/test_1.txt", 71),
     case(&"day_16/input.txt", 27870),
     )]
     fn test_err_rate(path: &str, exp_err_rate: usize) {
-        let contents = Input::read(&path);
+        let contents = Input::read(path);
         assert!(contents.is_ok());
         let contents = contents.unwrap();
         assert_eq!(contents.compute_err_rate(), exp_err_rate);
@@ -252,12 +326,13 @@ mod tests {
         "row", "departure track", "wagon", "type", "class", "departure platform",
         "departure station", "departure time")),
     )]
-    fn test_guess_fields(path: &str, columns: Vec<&str>) {
-        let contents = Input::read(&path);
+    fn test_assign_fields(path: &str, columns: Vec<&str>) {
+        let contents = Input::read(path);
         assert!(contents.is_ok());
         let mut contents = contents.unwrap();
         contents.discard_invalid_tickets();
-        assert_eq!(contents.guess_fields(), columns);
+        let columns = columns.iter().map(|&f| f.to_string()).collect();
+        assert_eq!(contents.assign_fields(), Assignment::Unique(columns));
     }
 
     #[rstest(path, columns, exp_check,
@@ -270,11 +345,11 @@ mod tests {
         "departure station", "departure time"), 3173135507987),
     )]
     fn test_check_ticket(path: &str, columns: Vec<&str>, exp_check: usize) {
-        let contents = Input::read(&path);
+        let contents = Input::read(path);
         assert!(contents.is_ok());
         let contents = contents.unwrap();
 
         let columns: Vec<String> = columns.iter().map(|&f| f.to_string()).collect();
-        assert_eq!(contents.check_my_ticket(&columns, &"departure"), exp_check);
+        assert_eq!(contents.check_my_ticket(&columns, "departure"), exp_check);
     }
 }
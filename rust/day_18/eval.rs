@@ -0,0 +1,202 @@
+use std::convert::TryFrom;
+use num_bigint::BigInt;
+
+use super::parser::{ErrorKind, Expr, ExprError};
+
+// How to evaluate: i64 failing on overflow, big integers,
+// or i64 first and big integers again only when it overflows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arithmetic {
+    Checked,
+    Big,
+    Auto,
+}
+
+impl Arithmetic {
+    pub fn from_name(name: &str) -> Option<Arithmetic> {
+        match name {
+            "checked" => Some(Arithmetic::Checked),
+            "big" => Some(Arithmetic::Big),
+            "auto" => Some(Arithmetic::Auto),
+            _ => None,
+        }
+    }
+}
+
+// A node still to be evaluated, or one whose operands are already on the value stack.
+enum Step<'a> {
+    Visit(&'a Expr),
+    Apply(&'a Expr),
+}
+
+// Division truncates toward zero, like i64 and BigInt do: -7 / 2 = -3.
+impl Expr {
+    // Post-order walk with an explicit stack: a long chain of left associative
+    // operators is a tree as deep as the chain, too deep to recurse on.
+    fn fold<T>(
+        &self,
+        number: impl Fn(&BigInt, usize) -> Result<T, ExprError>,
+        neg: impl Fn(T, usize) -> Result<T, ExprError>,
+        binary: impl Fn(char, usize, T, T) -> Result<T, ExprError>,
+    ) -> Result<T, ExprError> {
+        let mut steps = vec![Step::Visit(self)];
+        let mut values = Vec::new();
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Visit(Expr::Number { value, column }) => values.push(number(value, *column)?),
+                Step::Visit(expr) => {
+                    steps.push(Step::Apply(expr));
+                    match expr {
+                        Expr::Neg { operand, .. } => steps.push(Step::Visit(operand)),
+                        Expr::Binary { lhs, rhs, .. } => {
+                            steps.push(Step::Visit(rhs));
+                            steps.push(Step::Visit(lhs));
+                        }
+                        Expr::Number { .. } => unreachable!("numbers are visited above"),
+                    }
+                }
+                Step::Apply(Expr::Neg { column, .. }) => {
+                    let operand = values.pop().expect("operand evaluated");
+                    values.push(neg(operand, *column)?);
+                }
+                Step::Apply(Expr::Binary { op, column, .. }) => {
+                    let b = values.pop().expect("rhs evaluated");
+                    let a = values.pop().expect("lhs evaluated");
+                    values.push(binary(*op, *column, a, b)?);
+                }
+                Step::Apply(Expr::Number { .. }) => unreachable!("numbers are never applied"),
+            }
+        }
+        Ok(values.pop().expect("a value for the whole expression"))
+    }
+
+    pub fn eval(&self) -> Result<i64, ExprError> {
+        self.fold(
+            |value, column| i64::try_from(value).map_err(|_| ExprError::overflow(column)),
+            |operand: i64, column| operand.checked_neg().ok_or_else(|| ExprError::overflow(column)),
+            |op, column, a, b| {
+                let rv = match op {
+                    '+' => a.checked_add(b),
+                    '-' => a.checked_sub(b),
+                    '*' => a.checked_mul(b),
+                    '/' if b == 0 => return Err(ExprError::division_by_zero(column)),
+                    '/' => a.checked_div(b),  // only i64::MIN / -1 overflows
+                    _ => unreachable!("the parser only builds known operators"),
+                };
+                rv.ok_or_else(|| ExprError::overflow(column))
+            },
+        )
+    }
+
+    pub fn eval_big(&self) -> Result<BigInt, ExprError> {
+        self.fold(
+            |value, _| Ok(value.clone()),
+            |operand: BigInt, _| Ok(-operand),
+            |op, column, a, b| Ok(match op {
+                '+' => a + b,
+                '-' => a - b,
+                '*' => a * b,
+                '/' if b == BigInt::from(0) => return Err(ExprError::division_by_zero(column)),
+                '/' => a / b,
+                _ => unreachable!("the parser only builds known operators"),
+            }),
+        )
+    }
+
+    pub fn evaluate(&self, arithmetic: Arithmetic) -> Result<BigInt, ExprError> {
+        match arithmetic {
+            Arithmetic::Checked => self.eval().map(BigInt::from),
+            Arithmetic::Big => self.eval_big(),
+            Arithmetic::Auto => match self.eval() {
+                Err(e) if e.kind == ErrorKind::Overflow => self.eval_big(),
+                rv => rv.map(BigInt::from),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::parser::{parse, Table};
+    use rstest::rstest;
+
+    fn eval(expr: &str, arithmetic: Arithmetic) -> Result<BigInt, ExprError> {
+        parse(expr, &Table::addition_first()).unwrap().evaluate(arithmetic)
+    }
+
+    #[rstest(expr, exp,
+    case("-3", -3),
+    case("--3", 3),
+    case("2 * -3", -6),
+    case("-2 + 3", 1),
+    case("-(2 + 3) * 2", -10),
+    case("1 - -1", 2),
+    case("10 - 4 - 3", 3),
+    case("7 / 2", 3),
+    case("-7 / 2", -3),
+    case("7 / -2", -3),
+    case("2 + 6 / 2 + 1", 2),
+    )]
+    fn test_semantics(expr: &str, exp: i64) {
+        for arithmetic in [Arithmetic::Checked, Arithmetic::Big, Arithmetic::Auto] {
+            assert_eq!(eval(expr, arithmetic), Ok(BigInt::from(exp)), "{} {:?}", expr, arithmetic);
+        }
+    }
+
+    #[rstest(expr, exp_column,
+    case("9223372036854775807 + 1", 20),
+    case("9223372036854775808", 0),
+    case("-9223372036854775807 - 2", 21),
+    case("3037000500 * 3037000500", 11),
+    case("-(0 - 9223372036854775807 - 1)", 0),
+    case("(0 - 9223372036854775807 - 1) / -1", 30),
+    )]
+    fn test_checked_overflow(expr: &str, exp_column: usize) {
+        assert_eq!(eval(expr, Arithmetic::Checked), Err(ExprError::overflow(exp_column)));
+        let exp = parse(expr, &Table::addition_first()).unwrap().eval_big().unwrap();
+        assert_eq!(eval(expr, Arithmetic::Auto), Ok(exp.clone()));
+        assert!(exp > BigInt::from(i64::MAX) || exp < BigInt::from(i64::MIN));
+    }
+
+    #[test]
+    fn test_i64_min() {
+        // the literal itself does not fit, Auto gets there with big integers
+        let rv = eval("-9223372036854775808", Arithmetic::Auto);
+        assert_eq!(rv, Ok(BigInt::from(i64::MIN)));
+        assert!(eval("-9223372036854775808", Arithmetic::Checked).is_err());
+    }
+
+    #[test]
+    fn test_stress() {
+        // 30 factors of 999 999 937 and their sum, nested in parentheses
+        let factor = "999999937";
+        let product = vec![factor; 30].join(" * ");
+        let sum = vec![factor; 30].join(" + ");
+        let expr = format!("({}) * ({}) - 1", product, sum);
+
+        let f = BigInt::from(999999937u64);
+        // subtraction binds before multiplication with this table
+        let exp = f.pow(30) * (f * 30 - 1);
+        let rv = eval(&expr, Arithmetic::Big).unwrap();
+        assert_eq!(rv, exp);
+        assert!(rv.bits() > 900);
+        assert_eq!(eval(&expr, Arithmetic::Auto), Ok(exp));
+        assert_eq!(eval(&expr, Arithmetic::Checked).unwrap_err().kind, ErrorKind::Overflow);
+
+        // a flat chain is a tree as deep as the chain
+        let terms = 100_000;
+        let product = vec!["3"; terms].join(" * ");
+        assert_eq!(eval(&product, Arithmetic::Auto), Ok(BigInt::from(3).pow(terms as u32)));
+        // + and - bind alike, so this is 3 then (terms / 2 - 1) times -1 + 2
+        let sum = vec!["1 + 2"; terms / 2].join(" - ");
+        assert_eq!(eval(&sum, Arithmetic::Checked), Ok(BigInt::from(3 + (terms / 2 - 1))));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        for arithmetic in [Arithmetic::Checked, Arithmetic::Big, Arithmetic::Auto] {
+            assert_eq!(eval("1 / (2 - 2)", arithmetic), Err(ExprError::division_by_zero(2)));
+        }
+    }
+}
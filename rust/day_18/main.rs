@@ -2,119 +2,58 @@ use std::{io, env};
 use std::fs::File;
 use std::io::{BufReader, BufRead};
 
-fn to_expr(line: String) -> Vec<String> {
-    line
-        .replace("(", "( ")
-        .replace(")", " )")
-        .split_whitespace()
-        .map(|e| e.to_string())
-        .collect()
-}
-
-fn read(path: &str) -> io::Result<Vec<Vec<String>>> {
-    let file = File::open(path)?;
-    let br = BufReader::new(file);
-    let mut rv = Vec::new();
+use num_bigint::BigInt;
 
-    for line in br.lines() {
-        let line = line?;
-        rv.push(to_expr(line));
-    }
-    Ok(rv)
-}
+mod eval;
+mod parser;
+mod repl;
 
-// TODO
-fn prioritise_addition(expr: &[String]) -> Vec<String> {
-    let mut rv = Vec::new();
-    let mut push = 0;
-
-    for (i, b) in expr.iter().enumerate() {
-        rv.push(b.clone());
-        if b == "+" {
-            rv.insert(i - 1, "(".to_string());
-            push += 1;
-        }
-    }
+use eval::Arithmetic;
+use parser::Table;
 
-    rv
+fn read(path: &str) -> io::Result<Vec<String>> {
+    let file = File::open(path)?;
+    let br = BufReader::new(file);
+    br.lines().collect()
 }
 
-fn apply(op: &str, a: i64, b: i64) -> i64 {
-    match op {
-        "+" => a + b,
-        "-" => a - b,
-        "*" => a * b,
-        "/" => a / b,
-        _ => b  // no operation, just store the value
-    }
+fn eval_sum(exprs: &[String], table: &Table, arithmetic: Arithmetic) -> Result<BigInt, String> {
+    exprs.iter()
+        .enumerate()
+        .map(|(i, e)| parser::parse(e, table)
+            .and_then(|e| e.evaluate(arithmetic))
+            .map_err(|err| format!("line {}: {}\n{}", i + 1, err, err.render(e))))
+        .sum()
 }
 
-// no precedence
-fn evaluate(expr: &[String]) -> i64 {
-    let mut prev_block = 0;
-    let mut op = "";
-    let mut open = 0 as usize;
-    let mut acc = Vec::new();
-
-    // println!("store {}", prev_block);
-    for b in expr.iter() {
-        // println!("{} prev={} op={} open={} acc={:?}", b, prev_block, op, open, acc);
-        if b == "(" {
-            if open > 0 {
-                acc.push(b.clone());
-            }
-            open += 1;
-            continue;
-        } else if b == ")" {
-            open -= 1;
-
-            if open == 0 {
-                // we finished accumulating
-                let v = evaluate(&acc);
-                // println!("-> {} prev={} op={}", v, prev_block, op);
-                if op.is_empty() {
-                    prev_block = v;
-                } else {
-                    prev_block = apply(op, prev_block, v);
-                    op = "";
-                }
-                acc.clear();
-            } else {
-                acc.push(b.clone());
-            }
-            continue;
-        }
-
-        if open > 0 {
-            // we're in a parenthesis block, accumulate and compute on close
-            acc.push(b.clone());
-            continue;
-        }
-
-        let v = b.parse::<i64>();
-        match v {
-            Ok(v) => {
-                prev_block = apply(op, prev_block, v);
-                op = "";
-            },
-            _ => op = b
-        }
+fn main() {
+    let option = |name: &str| env::args()
+        .find_map(|a| a.strip_prefix(&format!("--{}=", name)).map(|v| v.to_string()));
+    let table = option("table");
+    let arithmetic = option("arithmetic")
+        .map_or(Arithmetic::Auto, |a| Arithmetic::from_name(&a).expect("arithmetic is checked, big or auto"));
+
+    if env::args().any(|a| a == "--repl") {
+        let table = Table::from_string(table.as_deref().unwrap_or("equal")).expect("invalid table");
+        let show_tree = env::args().any(|a| a == "--tree");
+        repl::run(io::stdin().lock(), io::stdout(), table, arithmetic, show_tree).expect("cannot run the repl");
+        return;
     }
 
-    println!("=> {} = {}", expr.join(" "), prev_block);
-    prev_block
-}
-
-fn eval_sum(exprs: &[Vec<String>]) -> i64 {
-    exprs.iter().map(|e| evaluate(&e)).sum()
-}
-
-fn main() {
     let path = env::args().nth(1).expect("please supply a path");
     let contents = read(&path).expect("no content");
 
-    let sum = eval_sum(&contents);
+    let sum = eval_sum(&contents, &Table::equal(), arithmetic).expect("invalid homework");
     println!("Q1: {}", sum);
+
+    let sum = eval_sum(&contents, &Table::addition_first(), arithmetic).expect("invalid homework");
+    println!("Q2: {}", sum);
+
+    if let Some(name) = table {
+        let table = Table::from_string(&name).expect("invalid table");
+        let sum = eval_sum(&contents, &table, arithmetic).expect("invalid homework");
+        println!("{}: {}", name, sum);
+    }
 }
 
 #[cfg(test)]
@@ -131,8 +70,19 @@ mod tests {
     case("((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2", 13632),
     )]
     fn test_evaluate(expr: &str, exp: i64) {
-        let expr: Vec<String> = to_expr(expr.to_string());
-        assert_eq!(evaluate(&expr), exp);
+        assert_eq!(parser::parse(expr, &Table::equal()).unwrap().eval().unwrap(), exp);
+    }
+
+    #[rstest(expr, exp,
+    case("1 + 2 * 3 + 4 * 5 + 6", 231),
+    case("1 + (2 * 3) + (4 * (5 + 6))", 51),
+    case("2 * 3 + (4 * 5)", 46),
+    case("5 + (8 * 3 + 9 + 3 * 4 * 3)", 1445),
+    case("5 * 9 * (7 * 3 * 3 + 9 * 3 + (8 + 6 * 4))", 669060),
+    case("((2 + 4 * 9) * (6 + 9 * 8 + 6) + 6) + 2 + 4 * 2", 23340),
+    )]
+    fn test_evaluate_addition_first(expr: &str, exp: i64) {
+        assert_eq!(parser::parse(expr, &Table::addition_first()).unwrap().eval().unwrap(), exp);
     }
 
     #[rstest(path, exp,
@@ -140,9 +90,42 @@ mod tests {
     case("day_18/input.txt", 9535936849815),
     )]
     fn test_eval_sum(path: &str, exp: i64) {
-        let contents = read(&path);
+        let contents = read(path);
         assert!(contents.is_ok());
         let contents = contents.unwrap();
-        assert_eq!(eval_sum(&contents), exp);
+        assert_eq!(eval_sum(&contents, &Table::equal(), Arithmetic::Checked), Ok(BigInt::from(exp)));
+    }
+
+    #[test]
+    fn test_eval_sum_reports_line() {
+        let contents = vec!["1 + 2".to_string(), "3 * (4".to_string()];
+        assert_eq!(
+            eval_sum(&contents, &Table::equal(), Arithmetic::Auto),
+            Err("line 2: column 5: unbalanced (\n3 * (4\n    ^ unbalanced (".to_string()),
+        );
+    }
+
+    #[rstest(path, exp,
+    case("day_18/test_1.txt", 231 + 46 + 1445 + 51 + 669060 + 23340),
+    case("day_18/input.txt", 472171581333710),
+    )]
+    fn test_eval_sum_addition_first(path: &str, exp: i64) {
+        let contents = read(path).unwrap();
+        assert_eq!(eval_sum(&contents, &Table::addition_first(), Arithmetic::Checked), Ok(BigInt::from(exp)));
+    }
+
+    #[test]
+    fn test_eval_sum_past_64_bits() {
+        let contents = vec![
+            "1 + 2".to_string(),
+            "(4294967296 * 4294967296) * 2".to_string(),
+        ];
+        let exp = BigInt::from(3) + (BigInt::from(1u8) << 65usize);
+        assert_eq!(eval_sum(&contents, &Table::equal(), Arithmetic::Auto), Ok(exp.clone()));
+        assert_eq!(eval_sum(&contents, &Table::equal(), Arithmetic::Big), Ok(exp));
+        assert_eq!(
+            eval_sum(&contents, &Table::equal(), Arithmetic::Checked),
+            Err("line 2: column 13: overflow\n(4294967296 * 4294967296) * 2\n            ^ overflow".to_string()),
+        );
     }
 }
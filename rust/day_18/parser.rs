@@ -0,0 +1,405 @@
+use std::{fmt, mem};
+use num_bigint::BigInt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Syntax,
+    DivisionByZero,
+    Overflow,
+}
+
+// What went wrong, and the column (from 0) to point at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExprError {
+    pub kind: ErrorKind,
+    pub column: usize,
+    pub message: String,
+}
+
+impl ExprError {
+    pub fn new(column: usize, message: &str) -> ExprError {
+        ExprError { kind: ErrorKind::Syntax, column, message: message.to_string() }
+    }
+
+    pub fn division_by_zero(column: usize) -> ExprError {
+        ExprError { kind: ErrorKind::DivisionByZero, column, message: "division by zero".to_string() }
+    }
+
+    pub fn overflow(column: usize) -> ExprError {
+        ExprError { kind: ErrorKind::Overflow, column, message: "overflow".to_string() }
+    }
+
+    // The line with a caret under the column.
+    pub fn render(&self, line: &str) -> String {
+        format!("{}\n{}^ {}", line, " ".repeat(self.column), self.message)
+    }
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column + 1, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenKind {
+    Number(String),  // digits, however many
+    Op(char),
+    Open,
+    Close,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub column: usize,  // from 0
+}
+
+pub fn tokenize(line: &str) -> Result<Vec<Token>, ExprError> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut rv = Vec::new();
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        let c = chars[idx];
+        let column = idx;
+        idx += 1;
+        let kind = match c {
+            ' ' | '\t' => continue,
+            '(' => TokenKind::Open,
+            ')' => TokenKind::Close,
+            '+' | '-' | '*' | '/' => TokenKind::Op(c),
+            '0'..='9' => {
+                while idx < chars.len() && chars[idx].is_ascii_digit() {
+                    idx += 1;
+                }
+                TokenKind::Number(chars[column..idx].iter().collect())
+            }
+            _ => return Err(ExprError::new(column, &format!("unknown token {:?}", c))),
+        };
+        rv.push(Token { kind, column });
+    }
+    Ok(rv)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Assoc {
+    Left,
+    Right,
+}
+
+// Binding power and associativity of each binary operator, higher binds first.
+#[derive(Debug, Clone)]
+pub struct Table {
+    ops: Vec<(char, u8, Assoc)>,
+}
+
+impl Table {
+    pub fn new(ops: &[(char, u8, Assoc)]) -> Table {
+        Table { ops: ops.to_vec() }
+    }
+
+    // Q1: everything evaluated left to right
+    pub fn equal() -> Table {
+        use Assoc::*;
+        Table::new(&[('+', 1, Left), ('-', 1, Left), ('*', 1, Left), ('/', 1, Left)])
+    }
+
+    // Q2: addition (and subtraction) before multiplication (and division)
+    pub fn addition_first() -> Table {
+        use Assoc::*;
+        Table::new(&[('+', 2, Left), ('-', 2, Left), ('*', 1, Left), ('/', 1, Left)])
+    }
+
+    // A preset name, or groups of operators with their precedence
+    // and an optional r for right associative: "+-:2 */:1r"
+    pub fn from_string(spec: &str) -> Result<Table, String> {
+        match spec {
+            "equal" => return Ok(Table::equal()),
+            "addition" => return Ok(Table::addition_first()),
+            _ => (),
+        }
+        let mut ops = Vec::new();
+        for group in spec.split_whitespace() {
+            let (chars, prec) = group.split_once(':')
+                .ok_or_else(|| format!("expected <operators>:<precedence>, got {}", group))?;
+            let (prec, assoc) = match prec.strip_suffix('r') {
+                Some(p) => (p, Assoc::Right),
+                None => (prec, Assoc::Left),
+            };
+            let prec: u8 = prec.parse().map_err(|e| format!("precedence {}: {}", prec, e))?;
+            for c in chars.chars() {
+                if !"+-*/".contains(c) {
+                    return Err(format!("unknown operator {}", c));
+                }
+                ops.push((c, prec, assoc));
+            }
+        }
+        Ok(Table { ops })
+    }
+
+    fn get(&self, op: char) -> Option<(u8, Assoc)> {
+        self.ops.iter()
+            .find(|(c, _, _)| *c == op)
+            .map(|&(_, prec, assoc)| (prec, assoc))
+    }
+}
+
+// Unary minus is not in the table: it always applies to the operand right after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Number {
+        value: BigInt,
+        column: usize,
+    },
+    Neg {
+        column: usize,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: char,
+        column: usize,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+}
+
+impl Expr {
+    // One node per line, operands indented under their operator.
+    pub fn tree(&self) -> String {
+        let mut rv = String::new();
+        self.write_tree(0, &mut rv);
+        rv
+    }
+
+    fn write_tree(&self, depth: usize, rv: &mut String) {
+        let indent = "  ".repeat(depth);
+        match self {
+            Expr::Number { value, .. } => *rv += &format!("{}{}\n", indent, value),
+            Expr::Neg { operand, .. } => {
+                *rv += &format!("{}neg\n", indent);
+                operand.write_tree(depth + 1, rv);
+            }
+            Expr::Binary { op, lhs, rhs, .. } => {
+                *rv += &format!("{}{}\n", indent, op);
+                lhs.write_tree(depth + 1, rv);
+                rhs.write_tree(depth + 1, rv);
+            }
+        }
+    }
+}
+
+// The default drop recurses once per node, which overflows the stack on a
+// long chain of operators: detach the operands and drop them from a list.
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let mut operands = Vec::new();
+        self.take_operands(&mut operands);
+        while let Some(mut expr) = operands.pop() {
+            expr.take_operands(&mut operands);
+        }
+    }
+}
+
+impl Expr {
+    fn take_operands(&mut self, rv: &mut Vec<Expr>) {
+        let leaf = || Expr::Number { value: BigInt::default(), column: 0 };
+        match self {
+            Expr::Number { .. } => {}
+            Expr::Neg { operand, .. } => rv.push(mem::replace(operand, leaf())),
+            Expr::Binary { lhs, rhs, .. } => {
+                rv.push(mem::replace(lhs, leaf()));
+                rv.push(mem::replace(rhs, leaf()));
+            }
+        }
+    }
+}
+
+// Pratt parser: operands are numbers, negated operands or parenthesised expressions,
+// operators are taken while they bind at least as tight as `min`.
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    end: usize,  // column after the last character
+    pos: usize,
+    table: &'a Table,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let rv = self.peek();
+        self.pos += 1;
+        rv
+    }
+
+    fn operand(&mut self) -> Result<Expr, ExprError> {
+        match self.next() {
+            Some(Token { kind: TokenKind::Number(digits), column }) => Ok(Expr::Number {
+                value: digits.parse().expect("only digits"),
+                column,
+            }),
+            Some(Token { kind: TokenKind::Op('-'), column }) => Ok(Expr::Neg {
+                column,
+                operand: Box::new(self.operand()?),
+            }),
+            Some(Token { kind: TokenKind::Open, column }) => {
+                let rv = self.expr(0)?;
+                match self.next() {
+                    Some(Token { kind: TokenKind::Close, .. }) => Ok(rv),
+                    _ => Err(ExprError::new(column, "unbalanced (")),
+                }
+            }
+            Some(t) => Err(ExprError::new(t.column, "expected a number")),
+            None => Err(ExprError::new(self.end, "expected a number")),
+        }
+    }
+
+    // u16 so a left associative operator of precedence 255 can still ask for 256
+    fn expr(&mut self, min: u16) -> Result<Expr, ExprError> {
+        let mut lhs = self.operand()?;
+
+        while let Some(Token { kind: TokenKind::Op(op), column }) = self.peek() {
+            let (prec, assoc) = self.table.get(op)
+                .ok_or_else(|| ExprError::new(column, &format!("operator {} is not in the table", op)))?;
+            let prec = prec as u16;
+            if prec < min {
+                break;
+            }
+            self.next();
+            let next_min = match assoc {
+                Assoc::Left => prec + 1,
+                Assoc::Right => prec,
+            };
+            let rhs = self.expr(next_min)?;
+            lhs = Expr::Binary { op, column, lhs: Box::new(lhs), rhs: Box::new(rhs) };
+        }
+        Ok(lhs)
+    }
+}
+
+pub fn parse(line: &str, table: &Table) -> Result<Expr, ExprError> {
+    let end = line.chars().count();
+    let mut parser = Parser { tokens: tokenize(line)?, end, pos: 0, table };
+    let rv = parser.expr(0)?;
+    match parser.peek() {
+        None => Ok(rv),
+        Some(Token { kind: TokenKind::Close, column }) => Err(ExprError::new(column, "unbalanced )")),
+        Some(t) => Err(ExprError::new(t.column, "expected an operator")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_tokenize() {
+        let rv: Vec<(TokenKind, usize)> = tokenize("12 *(3+4)").unwrap().into_iter()
+            .map(|t| (t.kind, t.column))
+            .collect();
+        assert_eq!(rv, vec![
+            (TokenKind::Number("12".to_string()), 0),
+            (TokenKind::Op('*'), 3),
+            (TokenKind::Open, 4),
+            (TokenKind::Number("3".to_string()), 5),
+            (TokenKind::Op('+'), 6),
+            (TokenKind::Number("4".to_string()), 7),
+            (TokenKind::Close, 8),
+        ]);
+        assert!(tokenize("1 % 2").is_err());
+    }
+
+    #[rstest(expr, exp,
+    case("2 * 3 + 4", 10),
+    case("2 + 3 * 4", 20),
+    case("10 - 2 - 3", 5),
+    case("100 / 10 / 5", 2),
+    case("(1 + 2) * (3 + 4)", 21),
+    )]
+    fn test_equal(expr: &str, exp: i64) {
+        assert_eq!(parse(expr, &Table::equal()).unwrap().eval().unwrap(), exp);
+    }
+
+    #[rstest(expr, exp,
+    case("2 * 3 + 4", 14),
+    case("2 + 3 * 4", 20),
+    case("2 * 3 - 1", 4),
+    case("10 - 2 + 3", 11),
+    case("8 / 2 + 2", 2),
+    )]
+    fn test_addition_first(expr: &str, exp: i64) {
+        assert_eq!(parse(expr, &Table::addition_first()).unwrap().eval().unwrap(), exp);
+    }
+
+    #[rstest(spec, expr, exp,
+    case("equal", "2 * 3 + 4", 10),
+    case("addition", "2 * 3 + 4", 14),
+    case("*/:2 +-:1", "2 + 3 * 4", 14),
+    case("+-*/:1r", "2 * 3 + 4", 14),
+    )]
+    fn test_table_from_string(spec: &str, expr: &str, exp: i64) {
+        let table = Table::from_string(spec).unwrap();
+        assert_eq!(parse(expr, &table).unwrap().eval().unwrap(), exp);
+    }
+
+    #[rstest(spec, case("+"), case("+:x"), case("%:1"), case("+:-1"))]
+    fn test_table_from_string_invalid(spec: &str) {
+        assert!(Table::from_string(spec).is_err());
+    }
+
+    #[test]
+    fn test_highest_precedence() {
+        let table = Table::from_string("+:255 *:254").unwrap();
+        assert_eq!(parse("1 + 2", &table).unwrap().eval().unwrap(), 3);
+        assert_eq!(parse("10 * 2 + 3 + 4", &table).unwrap().eval().unwrap(), 90);
+        let table = Table::from_string("-:255").unwrap();
+        assert_eq!(parse("10 - 2 - 3", &table).unwrap().eval().unwrap(), 5);
+        let table = Table::from_string("-:255r").unwrap();
+        assert_eq!(parse("10 - 2 - 3", &table).unwrap().eval().unwrap(), 11);
+        assert!(Table::from_string("+:256").is_err());
+    }
+
+    #[test]
+    fn test_right_associative() {
+        let table = Table::new(&[('-', 1, Assoc::Right)]);
+        assert_eq!(parse("10 - 2 - 3", &table).unwrap().eval().unwrap(), 11);
+        assert!(parse("1 + 2", &table).is_err());
+    }
+
+    #[rstest(expr, exp_column, exp_message,
+    case("(1 + 2", 0, "unbalanced ("),
+    case("1 + (2 * (3 + 4)", 4, "unbalanced ("),
+    case("1 + 2)", 5, "unbalanced )"),
+    case("1 +", 3, "expected a number"),
+    case("1 2", 2, "expected an operator"),
+    case("", 0, "expected a number"),
+    case("()", 1, "expected a number"),
+    case("* 2", 0, "expected a number"),
+    case("1 + a", 4, "unknown token 'a'"),
+    case("1 -", 3, "expected a number"),
+    case("- * 2", 2, "expected a number"),
+    )]
+    fn test_invalid(expr: &str, exp_column: usize, exp_message: &str) {
+        assert_eq!(parse(expr, &Table::equal()), Err(ExprError::new(exp_column, exp_message)));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let expr = parse("1 + 6 / (3 - 3)", &Table::addition_first()).unwrap();
+        let err = expr.eval().unwrap_err();
+        assert_eq!(err, ExprError::division_by_zero(6));
+        assert_eq!(err.render("1 + 6 / (3 - 3)"), "1 + 6 / (3 - 3)\n      ^ division by zero");
+    }
+
+    #[test]
+    fn test_tree() {
+        let expr = parse("1 + 2 * 3", &Table::addition_first()).unwrap();
+        assert_eq!(expr.tree(), "*\n  +\n    1\n    2\n  3\n");
+        let expr = parse("-(1 + 2)", &Table::equal()).unwrap();
+        assert_eq!(expr.tree(), "neg\n  +\n    1\n    2\n");
+    }
+}
@@ -0,0 +1,84 @@
+use std::io;
+use std::io::{BufRead, Write};
+
+use super::eval::Arithmetic;
+use super::parser::{self, Table};
+
+// Evaluate one expression per line.
+// :tree toggles printing the parse tree, :table <spec> changes the precedence table.
+pub fn run<R: BufRead, W: Write>(
+    input: R,
+    mut output: W,
+    mut table: Table,
+    arithmetic: Arithmetic,
+    mut show_tree: bool,
+) -> io::Result<()> {
+    write!(output, "> ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let line = line.trim_end();
+
+        if line == ":tree" {
+            show_tree = !show_tree;
+            writeln!(output, "tree {}", if show_tree { "on" } else { "off" })?;
+        } else if let Some(spec) = line.strip_prefix(":table ") {
+            match Table::from_string(spec.trim()) {
+                Ok(t) => {
+                    table = t;
+                    writeln!(output, "table {}", spec.trim())?;
+                }
+                Err(e) => writeln!(output, "error: {}", e)?,
+            }
+        } else if !line.is_empty() {
+            match parser::parse(line, &table) {
+                Ok(expr) => {
+                    if show_tree {
+                        write!(output, "{}", expr.tree())?;
+                    }
+                    match expr.evaluate(arithmetic) {
+                        Ok(v) => writeln!(output, "= {}", v)?,
+                        Err(e) => writeln!(output, "{}", e.render(line))?,
+                    }
+                }
+                Err(e) => writeln!(output, "{}", e.render(line))?,
+            }
+        }
+        write!(output, "> ")?;
+        output.flush()?;
+    }
+    writeln!(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(input: &str, table: Table, show_tree: bool) -> String {
+        let mut rv = Vec::new();
+        run(input.as_bytes(), &mut rv, table, Arithmetic::Auto, show_tree).unwrap();
+        String::from_utf8(rv).unwrap()
+    }
+
+    #[test]
+    fn test_session() {
+        let rv = session("1 + 2 * 3\n\n(1 + 2\n4 / 0\n-99999999999 * 99999999999\n", Table::equal(), false);
+        assert_eq!(rv, "> = 9\n\
+                        > > (1 + 2\n^ unbalanced (\n\
+                        > 4 / 0\n  ^ division by zero\n\
+                        > = -9999999999800000000001\n\
+                        > \n");
+    }
+
+    #[test]
+    fn test_commands() {
+        let rv = session("1 + 2 * 3\n:table addition\n1 + 2 * 3\n:tree\n1 * 2\n:table %:1\n", Table::equal(), false);
+        assert_eq!(rv, "> = 9\n\
+                        > table addition\n\
+                        > = 9\n\
+                        > tree on\n\
+                        > *\n  1\n  2\n= 2\n\
+                        > error: unknown operator %\n\
+                        > \n");
+    }
+}